@@ -0,0 +1,271 @@
+// where credentials come from. none of them end up on a command line, curl
+// reads them from its stdin and grafana live gets them as a header. logins
+// through grafana's form keep their session cookie in a cookie jar under
+// ~/.cache/graf between runs.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::os::unix::fs::DirBuilderExt as _;
+
+pub enum Auth {
+    Token(String),
+    // USER:PASS
+    Basic(String),
+    // USER:PASS for the login form
+    Session(String),
+    // the user name an auth proxy in front of grafana vouches for
+    Proxy(String),
+}
+
+impl Auth {
+    /// the Authorization header value, for tokens and basic auth
+    pub fn header(&self) -> Option<String> {
+        match self {
+            Auth::Token(token) => Some(format!("Bearer {token}")),
+            Auth::Basic(userpass) => Some(format!(
+                "Basic {}",
+                crate::base64::encode(userpass.as_bytes())
+            )),
+            Auth::Session(_) | Auth::Proxy(_) => None,
+        }
+    }
+
+    /// the token of `-t -`, or the password of `-u USER:-` and `--login
+    /// USER:-`, read from the first line of stdin so it isn't on the command
+    /// line
+    pub fn read_stdin(self) -> Result<Auth, String> {
+        self.read_secret(&mut std::io::stdin().lock())
+    }
+
+    fn read_secret(self, r: &mut impl BufRead) -> Result<Auth, String> {
+        let mut read = || {
+            let mut line = String::new();
+            r.read_line(&mut line)
+                .map_err(|err| format!("unable to read stdin: {err}"))?;
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                return Err("no credentials on stdin".to_string());
+            }
+            Ok(line.to_string())
+        };
+        Ok(match self {
+            Auth::Token(token) if token == "-" => Auth::Token(read()?),
+            Auth::Basic(userpass) => match userpass.strip_suffix(":-") {
+                Some(user) => Auth::Basic(format!("{user}:{}", read()?)),
+                None => Auth::Basic(userpass),
+            },
+            Auth::Session(userpass) => match userpass.strip_suffix(":-") {
+                Some(user) => Auth::Session(format!("{user}:{}", read()?)),
+                None => Auth::Session(userpass),
+            },
+            auth => auth,
+        })
+    }
+}
+
+/// credentials from a token file, a credential helper, $GRAFANA_TOKEN, the
+/// config profile, or a ~/.netrc entry for `url`'s host, in that order
+pub fn find(
+    token_file: Option<&str>,
+    helper: Option<&str>,
+    config: &HashMap<String, String>,
+    url: &str,
+) -> Result<Option<Auth>, String> {
+    let setting = |key: &str| config.get(key).map(String::as_str);
+    if let Some(path) = token_file {
+        return read_token(path).map(Some);
+    }
+    if let Some(helper) = helper {
+        return run_helper(helper).map(Some);
+    }
+    if let Some(token) = std::env::var("GRAFANA_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+    {
+        return Ok(Some(Auth::Token(token)));
+    }
+    if let Some(token) = setting("token") {
+        return Ok(Some(Auth::Token(token.to_string())));
+    }
+    if let Some(userpass) = setting("user") {
+        return Ok(Some(Auth::Basic(userpass.to_string())));
+    }
+    if let Some(userpass) = setting("login") {
+        return Ok(Some(Auth::Session(userpass.to_string())));
+    }
+    if let Some(user) = setting("proxy_user") {
+        return Ok(Some(Auth::Proxy(user.to_string())));
+    }
+    if let Some(path) = setting("token_file") {
+        return read_token(path).map(Some);
+    }
+    if let Some(helper) = setting("credential_helper") {
+        return run_helper(helper).map(Some);
+    }
+    Ok(netrc(url))
+}
+
+fn read_token(path: &str) -> Result<Auth, String> {
+    let token = std::fs::read_to_string(path)
+        .map_err(|err| format!("unable to read token file {path}: {err}"))?;
+    Ok(Auth::Token(token.trim().to_string()))
+}
+
+// the helper prints the token, like `pass show grafana`
+fn run_helper(helper: &str) -> Result<Auth, String> {
+    let output = std::process::Command::new("sh")
+        .args(["-c", helper])
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|err| format!("unable to run credential helper: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("credential helper {helper:?} failed"));
+    }
+    let token = String::from_utf8_lossy(&output.stdout);
+    let token = token.lines().next().unwrap_or_default().trim();
+    if token.is_empty() {
+        return Err(format!("credential helper {helper:?} printed no token"));
+    }
+    Ok(Auth::Token(token.to_string()))
+}
+
+// the host part of `url`
+fn host(url: &str) -> Option<&str> {
+    let host = url.split("://").nth(1).unwrap_or(url);
+    let host = host.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?;
+    match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next(),
+        None => host.split(':').next(),
+    }
+}
+
+// login and password for the url's host from ~/.netrc (or $NETRC), like curl
+// --netrc would use
+fn netrc(url: &str) -> Option<Auth> {
+    let path = std::env::var_os("NETRC")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            let home = std::env::var_os("HOME")?;
+            Some(std::path::Path::new(&home).join(".netrc"))
+        })?;
+    let text = std::fs::read_to_string(path).ok()?;
+    let host = host(url)?;
+
+    let mut words = text.split_whitespace();
+    let (mut matched, mut login, mut password) = (false, None, None);
+    while let Some(word) = words.next() {
+        match word {
+            "machine" | "default" => {
+                if matched {
+                    break;
+                }
+                matched = word == "default" || words.next() == Some(host);
+            }
+            "login" if matched => login = words.next(),
+            "password" if matched => password = words.next(),
+            "login" | "password" | "account" => {
+                words.next();
+            }
+            // macros run until an empty line, which split_whitespace loses
+            "macdef" => break,
+            _ => {}
+        }
+    }
+    Some(Auth::Basic(format!("{}:{}", login?, password?)))
+}
+
+// a grafana_session cookie from logging in with the form, kept in a curl
+// cookie jar so grafana's rotated tokens are picked up along the way
+pub struct Session {
+    pub jar: std::path::PathBuf,
+    pub user: String,
+    pub password: String,
+}
+
+impl Session {
+    /// the session for USER:PASS at `url`, in a cookie jar of its own
+    pub fn new(url: &str, userpass: &str) -> Result<Session, String> {
+        let Some((user, password)) = userpass.split_once(':') else {
+            return Err("login must be given as USER:PASS".to_string());
+        };
+        let dir = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|d| !d.is_empty())
+            .map(std::path::PathBuf::from)
+            .or_else(|| {
+                let home = std::env::var_os("HOME").filter(|h| !h.is_empty())?;
+                Some(std::path::Path::new(&home).join(".cache"))
+            })
+            .ok_or("no cache directory for the session cookie, set $HOME")?
+            .join("graf");
+        // only the user may read the sessions
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|err| format!("unable to create {}: {err}", dir.display()))?;
+        // curl reads a -b argument with a '=' in it as cookies, not a file
+        let name: String = format!("{}-{user}", host(url).unwrap_or(url))
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "-._".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Ok(Session {
+            jar: dir.join(format!("session-{name}")),
+            user: user.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    /// the unexpired session cookie in the jar, as a Cookie header
+    pub fn cookie(&self) -> Option<String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        let jar = std::fs::read_to_string(&self.jar).ok()?;
+        // netscape format, domain flag path secure expires name value
+        jar.lines()
+            .rev()
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .filter(|f| f.len() == 7 && f[5] == "grafana_session")
+            .find(|f| f[4].parse::<u64>().is_ok_and(|exp| exp == 0 || exp > now))
+            .map(|f| format!("Cookie: grafana_session={}", f[6]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_from_stdin() {
+        let read = |auth: Auth, input: &str| auth.read_secret(&mut input.as_bytes());
+        let Ok(Auth::Token(token)) = read(Auth::Token("-".into()), "tok\nnext\n") else {
+            panic!()
+        };
+        assert_eq!(token, "tok");
+        let Ok(Auth::Basic(userpass)) = read(Auth::Basic("admin:-".into()), "se:cret\r\n") else {
+            panic!()
+        };
+        assert_eq!(userpass, "admin:se:cret");
+        let Ok(Auth::Session(userpass)) = read(Auth::Session("admin:-".into()), "pw") else {
+            panic!()
+        };
+        assert_eq!(userpass, "admin:pw");
+        // given on the command line, stdin is left alone
+        let Ok(Auth::Basic(userpass)) = read(Auth::Basic("admin:pw".into()), "") else {
+            panic!()
+        };
+        assert_eq!(userpass, "admin:pw");
+        let Err(err) = read(Auth::Token("-".into()), "") else {
+            panic!()
+        };
+        assert_eq!(err, "no credentials on stdin");
+    }
+}
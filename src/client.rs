@@ -0,0 +1,564 @@
+use std::io::Write as _;
+
+use serde::de::DeserializeOwned;
+
+use crate::auth::Session;
+use crate::models::{self, Dashboard, Datasource, Folder, Org, SearchHit};
+
+/// tls options for curl and grafana live's openssl
+#[derive(Default)]
+pub struct Tls {
+    pub cacert: Option<String>,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub insecure: bool,
+}
+
+impl Tls {
+    pub fn curl_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        for (flag, path) in [
+            ("--cacert", &self.cacert),
+            ("--cert", &self.cert),
+            ("--key", &self.key),
+        ] {
+            if let Some(path) = path {
+                args.extend([flag.to_string(), path.clone()]);
+            }
+        }
+        if self.insecure {
+            args.push("--insecure".to_string());
+        }
+        args
+    }
+
+    /// `openssl s_client` arguments for connecting to `host`, which is
+    /// checked against the certificate like curl does
+    pub fn openssl_args(&self, host: &str) -> Vec<String> {
+        let mut args = vec![];
+        for (flag, path) in [
+            ("-CAfile", &self.cacert),
+            ("-cert", &self.cert),
+            ("-key", &self.key),
+        ] {
+            if let Some(path) = path {
+                args.extend([flag.to_string(), path.clone()]);
+            }
+        }
+        if !self.insecure {
+            args.push("-verify_return_error".to_string());
+            // s_client checks the chain but not whose certificate it is
+            let ip = host.trim_matches(|c| c == '[' || c == ']');
+            if ip.parse::<std::net::IpAddr>().is_ok() {
+                args.extend(["-verify_ip".to_string(), ip.to_string()]);
+            } else {
+                args.extend(["-verify_hostname".to_string(), host.to_string()]);
+            }
+        }
+        args
+    }
+}
+
+// every request goes through curl, the auth and tls options are whatever
+// curl arguments we were given. secrets are written to curl's stdin as a
+// config file instead, so they don't show up in the process list
+pub struct Client {
+    pub url: String,
+    args: Vec<String>,
+    secrets: Vec<String>,
+    session: Option<Session>,
+    debug: usize,
+    // where what the client is doing goes, nowhere unless the caller says
+    log: Box<dyn Fn(&str)>,
+}
+
+impl Client {
+    pub fn new(url: &str, args: Vec<String>, debug: usize) -> Client {
+        Client {
+            url: url.to_string(),
+            args,
+            secrets: vec![],
+            session: None,
+            debug,
+            log: Box::new(|_| {}),
+        }
+    }
+
+    /// sends what the client is doing to `log` a line at a time, the
+    /// queries when `debug` is 1 or more, the requests when it's over 1 and
+    /// the responses when it's over 2
+    pub fn log(&mut self, log: impl Fn(&str) + 'static) {
+        self.log = Box::new(log);
+    }
+
+    // logs the line `line` makes, if the caller wants it at `level`
+    pub(crate) fn trace(&self, level: usize, line: impl FnOnce() -> String) {
+        if self.debug >= level {
+            (self.log)(&line());
+        }
+    }
+
+    /// sends `header` ("Name: value") with every later request
+    pub fn header(&mut self, header: String) {
+        self.args.extend(["-H".to_string(), header]);
+    }
+
+    /// passes the curl option `name` (like "user" or "header") with `value`
+    /// through curl's stdin
+    pub fn secret(&mut self, name: &str, value: &str) {
+        self.secrets.push(config_line(name, value));
+    }
+
+    /// sends the session's cookie with every later request, logging in
+    /// first if there's no cookie yet and again whenever it's turned down
+    pub fn session(&mut self, session: Session) -> Result<(), Failure> {
+        let jar = session.jar.to_string_lossy().to_string();
+        self.args
+            .extend(["-b".to_string(), jar.clone(), "-c".to_string(), jar]);
+        let fresh = session.cookie().is_none();
+        self.session = Some(session);
+        if fresh {
+            self.login()?;
+        }
+        Ok(())
+    }
+
+    /// the session cookie, as a header for connections that don't use curl
+    pub fn cookie(&self) -> Option<String> {
+        self.session.as_ref()?.cookie()
+    }
+
+    // posts the session's user and password to the login form, curl keeps
+    // the cookie that comes back in the jar
+    fn login(&self) -> Result<(), Failure> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        let body = serde_json::json!({"user": session.user, "password": session.password});
+        let (status, output) = self.run("/login", &[], &config_line("data", &body.to_string()))?;
+        if status == 200 && self.cookie().is_some() {
+            return Ok(());
+        }
+        let message = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+            .ok()
+            .and_then(|res| res["message"].as_str().map(str::to_string));
+        let message = match (message, status) {
+            (Some(message), _) => message,
+            (None, 0) => transport_error(&output),
+            (None, status) => format!("{status} {}", reason(status)),
+        };
+        Err(Failure {
+            status,
+            message: format!("unable to log in as {}: {message}", session.user),
+        })
+    }
+
+    pub fn get(&self, path: &str) -> Result<serde_json::Value, Failure> {
+        self.request(path, &[])
+    }
+
+    pub fn post(&self, path: &str, body: &str) -> Result<serde_json::Value, Failure> {
+        self.request(path, &["-d", body])
+    }
+
+    /// the response to `path` as a `T`
+    pub fn get_as<T: DeserializeOwned>(&self, path: &str) -> Result<T, Failure> {
+        decode(path, &self.get(path)?)
+    }
+
+    /// all of the dashboards `search` finds, a page at a time
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchHit>, Failure> {
+        // grafana's largest page
+        const LIMIT: usize = 5000;
+        let params = search.params();
+        let mut results = vec![];
+        for page in 1.. {
+            let hits: Vec<SearchHit> = self.get_as(&format!(
+                "/api/search?{}&limit={LIMIT}&page={page}",
+                params.join("&")
+            ))?;
+            let n = hits.len();
+            results.extend(hits);
+            if n < LIMIT {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    pub fn dashboard(&self, uid: &str) -> Result<Dashboard, Failure> {
+        self.get_as(&format!("/api/dashboards/uid/{}", encode(uid)))
+    }
+
+    /// the uid of the folder titled `folder`, or with that uid
+    pub fn folder_uid(&self, folder: &str) -> Result<Option<String>, Failure> {
+        if folder.eq_ignore_ascii_case("general") {
+            return Ok(Some("general".to_string()));
+        }
+        Ok(self
+            .folders()?
+            .into_iter()
+            .find(|f| f.title == folder || f.uid == folder)
+            .map(|f| f.uid))
+    }
+
+    pub fn folders(&self) -> Result<Vec<Folder>, Failure> {
+        // grafana's largest page
+        const LIMIT: usize = 1000;
+        let mut folders = vec![];
+        for page in 1.. {
+            let page: Vec<Folder> =
+                self.get_as(&format!("/api/folders?limit={LIMIT}&page={page}"))?;
+            let n = page.len();
+            folders.extend(page);
+            if n < LIMIT {
+                break;
+            }
+        }
+        Ok(folders)
+    }
+
+    pub fn datasources(&self) -> Result<Vec<Datasource>, Failure> {
+        self.get_as("/api/datasources")
+    }
+
+    /// the orgs the user is a member of
+    pub fn orgs(&self) -> Result<Vec<Org>, Failure> {
+        self.get_as("/api/user/orgs")
+    }
+
+    /// the id of the org named `name`, looked up among the user's orgs and
+    /// then all of them, which only server admins can
+    pub fn org_id(&self, name: &str) -> Result<Option<i64>, Failure> {
+        if let Some(org) = self.orgs()?.into_iter().find(|o| o.name == name) {
+            return Ok(Some(org.id));
+        }
+        match self.get(&format!("/api/orgs/name/{}", encode(name))) {
+            Ok(org) => Ok(org.get("id").and_then(|id| id.as_i64())),
+            Err(Failure { status: 404, .. }) => Ok(None),
+            Err(failure) => Err(failure),
+        }
+    }
+
+    fn request(&self, path: &str, args: &[&str]) -> Result<serde_json::Value, Failure> {
+        let (mut status, mut output) = self.run(path, args, "")?;
+        if status == 401 && self.session.is_some() {
+            // the session expired or was logged out, log in again
+            self.login()?;
+            (status, output) = self.run(path, args, "")?;
+        }
+        self.trace(3, || format!("<- status: {status}"));
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            self.trace(3, || line.to_string());
+        }
+        if status == 0 {
+            return Err(Failure {
+                status,
+                message: transport_error(&output),
+            });
+        }
+
+        let json = serde_json::from_slice::<serde_json::Value>(&output.stdout);
+        self.trace(3, || match &json {
+            Ok(json) => format!("<- json: {json}"),
+            Err(_) => format!("<- text: {:?}", String::from_utf8_lossy(&output.stdout)),
+        });
+        let path = path.split('?').next().unwrap_or(path);
+        match json {
+            Ok(json) if (200..300).contains(&status) => Ok(json),
+            // failed datasource queries come back with their results, which
+            // say what went wrong with each query
+            Ok(json) if path == "/api/ds/query" && json.get("results").is_some() => Ok(json),
+            Err(err) if (200..300).contains(&status) => Err(Failure {
+                status,
+                message: format!(
+                    "unable to parse json response ({}{path}): {err}\n\
+                     hint: is {} grafana's base url, with any sub path?",
+                    self.url, self.url
+                ),
+            }),
+            json => {
+                // grafana explains itself in "message"
+                let message = json
+                    .ok()
+                    .and_then(|json| json.get("message")?.as_str().map(str::to_string));
+                let mut text = format!("{path}: {status} {}", reason(status));
+                if let Some(message) = &message {
+                    text = format!("{text}, {message}");
+                }
+                if let Some(hint) = hint(status, path, message.is_some()) {
+                    text = format!("{text}\nhint: {hint}");
+                }
+                Err(Failure {
+                    status,
+                    message: text,
+                })
+            }
+        }
+    }
+
+    // runs curl for `path`, with `secret` config lines on top of ours. the
+    // http status is 0 if there was no response
+    fn run(
+        &self,
+        path: &str,
+        args: &[&str],
+        secret: &str,
+    ) -> Result<(u16, std::process::Output), Failure> {
+        let url = &self.url;
+        let urlarg = format!("{url}{path}");
+        self.trace(2, || format!("-> get {:?} {urlarg:?} {args:?}", self.args));
+        let mut curl = std::process::Command::new("curl");
+        curl.args(&self.args).arg(&urlarg).args(args);
+        // the status goes on a line of its own after the body
+        curl.args(["-w", "\n%{http_code}"]);
+        let secrets = self.secrets.concat() + secret;
+        if !secrets.is_empty() {
+            curl.args(["-K", "-"]).stdin(std::process::Stdio::piped());
+        }
+        let failed = |err: std::io::Error| Failure {
+            status: 0,
+            message: format!("unable to run curl for {url}: {err}"),
+        };
+        let mut child = curl
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(failed)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(secrets.as_bytes()).map_err(failed)?;
+        }
+        let mut output = child.wait_with_output().map_err(failed)?;
+        let split = output.stdout.iter().rposition(|&b| b == b'\n');
+        let status = split
+            .and_then(|k| std::str::from_utf8(&output.stdout[k + 1..]).ok())
+            .and_then(|status| status.parse().ok())
+            .unwrap_or(0);
+        output.stdout.truncate(split.unwrap_or(0));
+        Ok((status, output))
+    }
+}
+
+/// what to look for in /api/search, dashboards of any kind by default
+#[derive(Clone, Default)]
+pub struct Search {
+    pub query: Option<String>,
+    pub tags: Vec<String>,
+    pub folder_uids: Vec<String>,
+    pub starred: bool,
+    pub sort: Option<String>,
+}
+
+impl Search {
+    fn params(&self) -> Vec<String> {
+        let mut params = vec!["type=dash-db".to_string()];
+        if let Some(ref query) = self.query {
+            params.push(format!("query={}", encode(query)));
+        }
+        for tag in &self.tags {
+            params.push(format!("tag={}", encode(tag)));
+        }
+        for uid in &self.folder_uids {
+            params.push(format!("folderUIDs={}", encode(uid)));
+        }
+        if self.starred {
+            params.push("starred=true".to_string());
+        }
+        if let Some(ref sort) = self.sort {
+            params.push(format!("sort={}", encode(sort)));
+        }
+        params
+    }
+}
+
+/// a request that went wrong, `status` is 0 if there was no response
+#[derive(Debug)]
+pub struct Failure {
+    pub status: u16,
+    message: String,
+}
+
+/// `json`, the response to `path`, as a `T`
+pub fn decode<T: DeserializeOwned>(path: &str, json: &serde_json::Value) -> Result<T, Failure> {
+    models::decode(json).map_err(|err| Failure {
+        status: 200,
+        message: format!(
+            "unexpected response from {}: {err}",
+            path.split('?').next().unwrap_or(path)
+        ),
+    })
+}
+
+impl Failure {
+    /// the exit code for the kind of failure, see the usage text
+    pub fn code(&self) -> i32 {
+        match self.status {
+            0 => 3,
+            401 => 4,
+            403 => 5,
+            404 => 6,
+            500..=599 => 7,
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+// what to do about a failed request to `path`. `grafana` is whether the
+// response came from grafana rather than something in front of it
+fn hint(status: u16, path: &str, grafana: bool) -> Option<String> {
+    // the permission each api needs with grafana's access control
+    let permission = [
+        ("/api/search", "dashboards:read"),
+        ("/api/dashboards/", "dashboards:read"),
+        ("/api/folders", "folders:read"),
+        ("/api/ds/query", "datasources:query"),
+        ("/api/datasources/", "datasources:read"),
+        ("/api/orgs/", "orgs:read"),
+    ]
+    .iter()
+    .find(|(prefix, _)| path.starts_with(prefix))
+    .map(|(_, permission)| *permission);
+    Some(match status {
+        401 => {
+            "the token or password was turned down, it may be wrong, expired or revoked".to_string()
+        }
+        403 => match permission {
+            Some(permission) => format!("the token or user lacks {permission}"),
+            None => "the token or user lacks the permission for this".to_string(),
+        },
+        404 if path.starts_with("/api/dashboards/") => {
+            "the dashboard may have been deleted, or be in another org (--org)".to_string()
+        }
+        404 if path.starts_with("/api/datasources/") => {
+            "the panel's datasource may have been deleted or renamed".to_string()
+        }
+        404 if !grafana => "is the url grafana's base url, with any sub path?".to_string(),
+        502..=504 => "grafana, or a proxy in front of it, is down or unreachable".to_string(),
+        400 | 500 if path.starts_with("/api/ds/query") => {
+            "the datasource turned down the query".to_string()
+        }
+        _ => return None,
+    })
+}
+
+// why curl got no response, with a hint for certificate problems
+fn transport_error(output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // curl -sS says "curl: (60) SSL certificate problem: ..."
+    let message = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("curl: "))
+        .map(|line| line.split_once(") ").map_or(line, |(_, m)| m))
+        .unwrap_or("no response");
+    let hint = match output.status.code() {
+        Some(60 | 83) => {
+            "grafana's certificate isn't trusted, give the CA that signed it with --cacert \
+             (or skip verification with --insecure)"
+        }
+        Some(77) => "unable to use the CA certificates, check the --cacert file",
+        Some(58) => "unable to use the client certificate, check the --cert and --key files",
+        // handshakes cut short are often a client certificate that was
+        // missing or turned down
+        Some(35 | 56) if message.contains("SSL") || message.contains("alert") => {
+            "the tls handshake failed, grafana may want a client certificate \
+             (--cert and --key)"
+        }
+        _ => return message.to_string(),
+    };
+    format!("{message}\nhint: {hint}")
+}
+
+// a line of a curl config file setting `name` to `value`
+fn config_line(name: &str, value: &str) -> String {
+    let mut line = format!("{name} = \"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => line.extend(['\\', c]),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c => line.push(c),
+        }
+    }
+    line.push_str("\"\n");
+    line
+}
+
+/// `s` percent encoded for a query string
+pub fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_levels() {
+        let lines = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let mut client = Client::new("http://grafana", vec![], 2);
+        client.trace(1, || "unheard".to_string());
+        let log = lines.clone();
+        client.log(move |line| log.borrow_mut().push(line.to_string()));
+        client.trace(1, || "request".to_string());
+        client.trace(2, || "curl".to_string());
+        client.trace(3, || panic!("not made above the client's level"));
+        assert_eq!(*lines.borrow(), ["request", "curl"]);
+    }
+
+    #[test]
+    fn openssl_verifies_host() {
+        let tls = Tls {
+            cacert: Some("ca.pem".to_string()),
+            ..Tls::default()
+        };
+        let args = |host| tls.openssl_args(host).join(" ");
+        assert_eq!(
+            args("grafana.example"),
+            "-CAfile ca.pem -verify_return_error -verify_hostname grafana.example"
+        );
+        assert_eq!(
+            args("127.0.0.1"),
+            "-CAfile ca.pem -verify_return_error -verify_ip 127.0.0.1"
+        );
+        assert_eq!(
+            args("[::1]"),
+            "-CAfile ca.pem -verify_return_error -verify_ip ::1"
+        );
+        let insecure = Tls {
+            insecure: true,
+            ..Tls::default()
+        };
+        assert!(insecure.openssl_args("grafana.example").is_empty());
+    }
+}
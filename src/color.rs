@@ -0,0 +1,294 @@
+// series colors, as SGR parameters for the terminal's color support. with
+// 256 colors or truecolor, series get grafana's classic palette or the color
+// the panel or datasource gave them, like in the browser. pictures drawn in
+// pixels get them as rgb.
+
+use crate::models::Panel;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Never,
+    Ansi,
+    Ansi256,
+    TrueColor,
+}
+
+impl Mode {
+    /// `flag` is --color's auto, always or never, `tty` whether stdout is a
+    /// terminal and `env` the value of an environment variable, or nothing
+    pub fn detect(flag: &str, tty: bool, env: impl Fn(&str) -> String) -> Option<Mode> {
+        let depth = || {
+            let (colorterm, term) = (env("COLORTERM"), env("TERM"));
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                Mode::TrueColor
+            } else if term.contains("256color") {
+                Mode::Ansi256
+            } else {
+                Mode::Ansi
+            }
+        };
+        match flag {
+            "always" => Some(depth()),
+            "never" => Some(Mode::Never),
+            // https://no-color.org
+            "auto" if !tty || !env("NO_COLOR").is_empty() || env("TERM") == "dumb" => {
+                Some(Mode::Never)
+            }
+            "auto" => Some(depth()),
+            _ => None,
+        }
+    }
+}
+
+const ANSI: [&str; 6] = ["31", "32", "33", "34", "35", "36"];
+
+// the basic colors by hue, terminal themes change their shade but not their hue
+const ANSI_HUES: [&str; 6] = ["31", "33", "32", "36", "34", "35"];
+
+// grafana's classic palette, series without a color of their own go through it
+const CLASSIC: [u32; 56] = [
+    0x7eb26d, 0xeab839, 0x6ed0e0, 0xef843c, 0xe24d42, 0x1f78c1, 0xba43a9, 0x705da0, 0x508642,
+    0xcca300, 0x447ebc, 0xc15c17, 0x890f02, 0x0a437c, 0x6d1f62, 0x584477, 0xb7dbab, 0xf4d598,
+    0x70dbed, 0xf9ba8f, 0xf29191, 0x82b5d8, 0xe5a8e2, 0xaea2e0, 0x629e51, 0xe5ac0e, 0x64b0c8,
+    0xe0752d, 0xbf1b00, 0x0a50a1, 0x962d82, 0x614d93, 0x9ac48a, 0xf2c96d, 0x65c5db, 0xf9934e,
+    0xea6460, 0x5195ce, 0xd683ce, 0x806eb7, 0x3f6833, 0x967302, 0x2f575e, 0x99440a, 0x58140c,
+    0x052b51, 0x511749, 0x3f2b5b, 0xe0f9d7, 0xfceaca, 0xcffaff, 0xf9e2d2, 0xfce2de, 0xbadff4,
+    0xf9d9f9, 0xdedaf7,
+];
+
+// grafana's named colors, super-light, light, the hue itself, semi-dark and dark
+const NAMED: [(&str, [u32; 5]); 6] = [
+    ("red", [0xffa6b0, 0xff7383, 0xf2495c, 0xe02f44, 0xc4162a]),
+    ("orange", [0xffcb7d, 0xffb357, 0xff9830, 0xff780a, 0xfa6400]),
+    ("yellow", [0xfff899, 0xffee52, 0xfade2a, 0xf2cc0c, 0xe0b400]),
+    ("green", [0xc8f2c2, 0x96d98d, 0x73bf69, 0x56a64b, 0x37872d]),
+    ("blue", [0xc0d8ff, 0x8ab8ff, 0x5794f2, 0x3274d9, 0x1f60c4]),
+    ("purple", [0xdeb6f2, 0xca95e5, 0xb877d9, 0xa352cc, 0x8f3bb8]),
+];
+
+/// a color as grafana writes them, a name like 'semi-dark-green', '#73bf69'
+/// or 'rgb(115, 191, 105)'
+fn parse(color: &str) -> Option<u32> {
+    let color = color.trim().to_ascii_lowercase();
+    if let Some(hex) = color.strip_prefix('#') {
+        return match hex.len() {
+            6 | 8 => u32::from_str_radix(&hex[..6], 16).ok(),
+            3 | 4 => {
+                let rgb = u32::from_str_radix(&hex[..3], 16).ok()?;
+                let (r, g, b) = (rgb >> 8, rgb >> 4 & 0xf, rgb & 0xf);
+                Some((r * 0x11) << 16 | (g * 0x11) << 8 | (b * 0x11))
+            }
+            _ => None,
+        };
+    }
+    if let Some(args) = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))
+    {
+        let mut rgb = args.trim_end_matches(')').split(',');
+        let mut next = || rgb.next()?.trim().parse::<u8>().ok().map(u32::from);
+        return Some(next()? << 16 | next()? << 8 | next()?);
+    }
+    let (shade, name) = [
+        ("super-light-", 0),
+        ("light-", 1),
+        ("semi-dark-", 3),
+        ("dark-", 4),
+    ]
+    .into_iter()
+    .find_map(|(prefix, shade)| Some((shade, color.strip_prefix(prefix)?)))
+    .unwrap_or((2, &color));
+    NAMED
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, shades)| shades[shade])
+}
+
+fn sgr(mode: Mode, rgb: u32) -> String {
+    let (r, g, b) = (rgb >> 16 & 0xff, rgb >> 8 & 0xff, rgb & 0xff);
+    match mode {
+        Mode::Never => String::new(),
+        Mode::Ansi => {
+            let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+            let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+            let hue = if max == min {
+                0.0
+            } else if max == r {
+                (g - b) / (max - min)
+            } else if max == g {
+                (b - r) / (max - min) + 2.0
+            } else {
+                (r - g) / (max - min) + 4.0
+            };
+            // hue in sixths of the circle, red at 0
+            let k = hue.rem_euclid(6.0).round() as usize % 6;
+            ANSI_HUES[k].to_string()
+        }
+        // the 6x6x6 color cube
+        Mode::Ansi256 => {
+            let c = |v: u32| (v * 5 + 127) / 255;
+            format!("38;5;{}", 16 + 36 * c(r) + 6 * c(g) + c(b))
+        }
+        Mode::TrueColor => format!("38;2;{r};{g};{b}"),
+    }
+}
+
+// each series' own color, picked like grafana does by the panel's field
+// config overrides for the series' `names`, then the color configs the
+// datasource gave in `ds_colors`, then the panel's defaults
+fn picked(panel: &Panel, names: &[String], ds_colors: &[serde_json::Value]) -> Vec<Option<u32>> {
+    let fixed = |color: &serde_json::Value| match color["mode"].as_str() {
+        Some("fixed") | Some("shades") => color["fixedColor"].as_str().and_then(parse),
+        _ => None,
+    };
+    let field_config = &panel.field_config;
+    let default = fixed(&field_config["defaults"]["color"]);
+    let overrides = field_config["overrides"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let by_name = |name: &str| {
+        overrides
+            .iter()
+            .filter(|o| o["matcher"]["id"] == "byName" && o["matcher"]["options"] == name)
+            .flat_map(|o| o["properties"].as_array().into_iter().flatten())
+            .filter(|p| p["id"] == "color")
+            .find_map(|p| fixed(&p["value"]))
+    };
+    names
+        .iter()
+        .enumerate()
+        .map(|(k, name)| {
+            by_name(name)
+                .or_else(|| fixed(ds_colors.get(k)?))
+                .or(default)
+        })
+        .collect()
+}
+
+/// SGR parameters for each series, their own colors or the palette's. no
+/// colors at all for `Mode::Never`
+pub fn series_colors(
+    mode: Mode,
+    panel: &Panel,
+    names: &[String],
+    ds_colors: &[serde_json::Value],
+) -> Vec<String> {
+    if mode == Mode::Never {
+        return vec![];
+    }
+    picked(panel, names, ds_colors)
+        .into_iter()
+        .enumerate()
+        .map(|(k, rgb)| match (mode, rgb) {
+            (_, Some(rgb)) => sgr(mode, rgb),
+            // keep the plain colors the terminal's theme picked
+            (Mode::Ansi, None) => ANSI[k % ANSI.len()].to_string(),
+            (_, None) => sgr(mode, CLASSIC[k % CLASSIC.len()]),
+        })
+        .collect()
+}
+
+/// 0xrrggbb for each series, for drawing them in pixels like the browser does
+pub fn series_rgb(panel: &Panel, names: &[String], ds_colors: &[serde_json::Value]) -> Vec<u32> {
+    picked(panel, names, ds_colors)
+        .into_iter()
+        .enumerate()
+        .map(|(k, rgb)| rgb.unwrap_or(CLASSIC[k % CLASSIC.len()]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colors() {
+        for (color, rgb) in [
+            ("#73bf69", Some(0x73bf69)),
+            ("#73BF69", Some(0x73bf69)),
+            ("#73bf6980", Some(0x73bf69)),
+            ("#abc", Some(0xaabbcc)),
+            ("#abcd", Some(0xaabbcc)),
+            (" rgb(115, 191, 105) ", Some(0x73bf69)),
+            ("rgba(115,191,105,0.5)", Some(0x73bf69)),
+            ("green", Some(0x73bf69)),
+            ("semi-dark-green", Some(0x56a64b)),
+            ("super-light-red", Some(0xffa6b0)),
+            ("dark-purple", Some(0x8f3bb8)),
+            ("#12", None),
+            ("#ggghhh", None),
+            ("rgb(300, 0, 0)", None),
+            ("rgb(1, 2)", None),
+            ("chartreuse", None),
+            ("light-", None),
+            ("", None),
+        ] {
+            assert_eq!(parse(color), rgb, "{color:?}");
+        }
+    }
+
+    #[test]
+    fn sgr_depths() {
+        let green = 0x73bf69;
+        assert_eq!(sgr(Mode::TrueColor, green), "38;2;115;191;105");
+        assert_eq!(sgr(Mode::Ansi256, green), "38;5;114");
+        assert_eq!(sgr(Mode::Ansi256, 0x000000), "38;5;16");
+        assert_eq!(sgr(Mode::Ansi256, 0xffffff), "38;5;231");
+        // the basic color nearest in hue
+        for (rgb, ansi) in [
+            (green, "32"),
+            (0xf2495c, "31"),
+            (0xfade2a, "33"),
+            (0x6ed0e0, "36"),
+            (0x1f60c4, "34"),
+            (0xb877d9, "35"),
+            (0x808080, "31"),
+        ] {
+            assert_eq!(sgr(Mode::Ansi, rgb), ansi, "{rgb:06x}");
+        }
+        assert_eq!(sgr(Mode::Never, green), "");
+    }
+
+    #[test]
+    fn detect_mode() {
+        let full = [("COLORTERM", "truecolor"), ("TERM", "xterm-256color")];
+        for (flag, tty, vars, mode) in [
+            ("auto", true, &full[..], Some(Mode::TrueColor)),
+            (
+                "auto",
+                true,
+                &[("COLORTERM", "24bit")][..],
+                Some(Mode::TrueColor),
+            ),
+            ("auto", true, &full[1..], Some(Mode::Ansi256)),
+            ("auto", true, &[("TERM", "xterm")][..], Some(Mode::Ansi)),
+            ("auto", true, &[][..], Some(Mode::Ansi)),
+            // NO_COLOR wins over both, and a dumb terminal has none
+            (
+                "auto",
+                true,
+                &[("NO_COLOR", "1"), ("COLORTERM", "truecolor")][..],
+                Some(Mode::Never),
+            ),
+            ("auto", true, &[("TERM", "dumb")][..], Some(Mode::Never)),
+            ("auto", false, &full[..], Some(Mode::Never)),
+            // unless colors are asked for
+            (
+                "always",
+                false,
+                &[("NO_COLOR", "1"), ("TERM", "xterm-256color")][..],
+                Some(Mode::Ansi256),
+            ),
+            ("never", true, &full[..], Some(Mode::Never)),
+            ("sometimes", true, &full[..], None),
+        ] {
+            let env = |name: &str| {
+                (vars.iter())
+                    .find(|(n, _)| *n == name)
+                    .map_or(String::new(), |(_, v)| v.to_string())
+            };
+            assert_eq!(Mode::detect(flag, tty, env), mode, "{flag} {vars:?}");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// ~/.config/graf/config, named profiles of default options like
+//
+//   # settings before any profile apply to all of them
+//   color = always
+//
+//   [default]
+//   url = http://localhost:3000
+//   user = admin:admin
+//
+//   [prod]
+//   url = https://grafana.example.com
+//   credential_helper = pass show grafana
+//   org = ops
+//   from = now-1h
+//
+// the default profile is used unless --profile names another. flags given on
+// the command line win over the profile.
+
+use std::collections::HashMap;
+
+const KEYS: [&str; 21] = [
+    "url",
+    "user",
+    "token",
+    "token_file",
+    "credential_helper",
+    "login",
+    "proxy_user",
+    "proxy_header",
+    "cacert",
+    "cert",
+    "key",
+    "insecure",
+    "org",
+    "from",
+    "to",
+    "interval",
+    "tz",
+    "color",
+    "graphics",
+    "width",
+    "height",
+];
+
+/// $XDG_CONFIG_HOME/graf/config, or ~/.config/graf/config
+pub fn path() -> Option<std::path::PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            let home = std::env::var_os("HOME").filter(|h| !h.is_empty())?;
+            Some(std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(dir.join("graf").join("config"))
+}
+
+/// the settings of `profile`, or the default profile. it's only an error for
+/// a profile that was asked for to be missing
+pub fn load(profile: Option<&str>) -> Result<HashMap<String, String>, String> {
+    let path = path();
+    let text = match path.as_ref().map(std::fs::read_to_string) {
+        Some(Ok(text)) => text,
+        Some(Err(err)) if err.kind() != std::io::ErrorKind::NotFound => {
+            let path = path.unwrap();
+            return Err(format!("unable to read {}: {err}", path.display()));
+        }
+        _ if profile.is_some() => {
+            return Err("no config file to read profiles from".to_string());
+        }
+        _ => return Ok(HashMap::new()),
+    };
+    let path = path.unwrap();
+    let want = profile.unwrap_or("default");
+
+    let mut settings = HashMap::new();
+    let mut section: Option<&str> = None;
+    let mut found = false;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim());
+            found |= section == Some(want);
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!(
+                "{}:{}: expected 'key = value' or '[profile]'",
+                path.display(),
+                n + 1
+            ));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if !KEYS.contains(&key) {
+            eprintln!(
+                "warning: {}:{}: unknown setting {key:?}",
+                path.display(),
+                n + 1
+            );
+            continue;
+        }
+        if section.is_none() || section == Some(want) {
+            settings.insert(key.to_string(), value.to_string());
+        }
+    }
+    if !found && profile.is_some() {
+        return Err(format!("no profile [{want}] in {}", path.display()));
+    }
+    Ok(settings)
+}
@@ -0,0 +1,268 @@
+// following a panel's query as time goes on. the next rows are polled for
+// every interval, or pushed over grafana live for streaming datasources with
+// the values of frames that didn't change carried over.
+
+use crate::client::{Failure, Tls};
+use crate::live::Live;
+use crate::models::{decode, Frame};
+use crate::query::{Fetch, Query};
+use crate::render::Frames;
+
+pub struct Follow<'c> {
+    pub query: Query<'c>,
+    // the range the rows so far came from
+    pub from: i64,
+    pub to: i64,
+    // a time shifted panel trails now by the shift
+    shift: i64,
+    // the end of the range the last rows came from, polls start an
+    // interval before it until there are new rows
+    rows_to: i64,
+    // the time of the last row, in milliseconds
+    time: i64,
+    ncurrent: usize,
+    // the last value of each series
+    last: Vec<Vec<Option<f64>>>,
+    // the grafana live channel of each current frame
+    channels: Vec<Option<String>>,
+    live: Option<Live>,
+}
+
+/// what waiting for the rows after the last ones came to
+pub enum Next {
+    Rows {
+        times: Vec<i64>,
+        vals: Frames,
+        ncurrent: usize,
+    },
+    /// the query ran but had nothing, the next interval is tried next time
+    NoData,
+    /// the query failed, maybe the datasource is back by the next interval
+    Failed(String),
+    /// grafana live went away and the rows are polled for from now on
+    Polling(String),
+    /// the wait was cut short
+    Interrupted,
+}
+
+fn now() -> i64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    i64::try_from(now).unwrap()
+}
+
+// sleeps for `dur`, or until `interrupted`, saying which
+fn sleep(dur: std::time::Duration, interrupted: &dyn Fn() -> bool) -> bool {
+    let end = std::time::Instant::now() + dur;
+    while !interrupted() {
+        let left = end.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        std::thread::sleep(left.min(std::time::Duration::from_millis(100)));
+    }
+    true
+}
+
+fn last_vals(vals: &Frames) -> Vec<Vec<Option<f64>>> {
+    vals.iter()
+        .map(|vvs| vvs.iter().map(|vs| vs.last().copied().flatten()).collect())
+        .collect()
+}
+
+// the row after `last` with frame `k`'s series set to the last of `series`,
+// and the two rows as frames
+fn push_row(
+    last: &[Vec<Option<f64>>],
+    k: usize,
+    series: &[Vec<Option<f64>>],
+) -> (Vec<Vec<Option<f64>>>, Frames) {
+    let mut row = last.to_vec();
+    for (s, v) in row[k].iter_mut().enumerate() {
+        *v = series.get(s).and_then(|vs| *vs.last()?);
+    }
+    let vals = last
+        .iter()
+        .zip(&row)
+        .map(|(l, r)| l.iter().zip(r).map(|(&l, &r)| vec![l, r]).collect())
+        .collect();
+    (row, vals)
+}
+
+impl<'c> Follow<'c> {
+    /// runs `query` for `from`..`to` to follow it on from there, failing if
+    /// grafana couldn't be asked at all
+    pub fn start(
+        query: Query<'c>,
+        from: i64,
+        to: i64,
+        shift: i64,
+    ) -> Result<(Follow<'c>, Fetch), Failure> {
+        let mut follow = Follow {
+            query,
+            from,
+            to,
+            shift,
+            rows_to: to,
+            time: to * 1000,
+            ncurrent: 0,
+            last: vec![],
+            channels: vec![],
+            live: None,
+        };
+        let fetch = follow.fetch()?;
+        if let Ok(Some(fetched)) = &fetch.data {
+            follow.channels = fetched.channels.clone();
+        }
+        Ok((follow, fetch))
+    }
+
+    /// runs the query again for the `span` up to the end of the rows, as
+    /// after the query was rebuilt for another size
+    pub fn restart(&mut self, span: i64) -> Result<Fetch, Failure> {
+        self.from = self.to - span;
+        self.fetch()
+    }
+
+    fn fetch(&mut self) -> Result<Fetch, Failure> {
+        let fetch = self.query.fetch(self.from, self.to)?;
+        if let Ok(Some(fetched)) = &fetch.data {
+            self.rows(self.to, &fetched.times, &fetched.vals, fetched.ncurrent);
+        }
+        Ok(fetch)
+    }
+
+    fn rows(&mut self, to: i64, times: &[i64], vals: &Frames, ncurrent: usize) {
+        self.rows_to = to;
+        self.time = times.last().copied().unwrap_or(to * 1000);
+        self.ncurrent = ncurrent;
+        self.last = last_vals(vals);
+    }
+
+    /// subscribes to the channels of streaming datasources over grafana live,
+    /// if there are any, for the rows to come as they're published
+    pub fn stream(&mut self, url: &str, headers: &[String], tls: &Tls) -> Result<(), String> {
+        if self.query.compare.is_some() || self.channels.iter().all(Option::is_none) {
+            return Ok(());
+        }
+        // the session cookie may have been renewed since starting
+        let mut headers = headers.to_vec();
+        headers.extend(self.query.client.cookie());
+        let mut live = Live::connect(url, &headers, tls)?;
+        for channel in self.channels.iter().flatten() {
+            live.subscribe(channel)?;
+        }
+        self.live = Some(live);
+        Ok(())
+    }
+
+    /// waits for the rows after the last ones, until `interrupted` says to
+    /// stop waiting. the datasource's notices are added to `notices`
+    pub fn next(
+        &mut self,
+        interrupted: &dyn Fn() -> bool,
+        notices: &mut Vec<String>,
+    ) -> Result<Next, Failure> {
+        if self.live.is_some() {
+            return Ok(match self.push(interrupted) {
+                Ok(Some(next)) => next,
+                Ok(None) => Next::Interrupted,
+                Err(err) => {
+                    self.live = None;
+                    Next::Polling(err)
+                }
+            });
+        }
+        let interval = self.query.interval;
+        let mut next = self.to + interval;
+        let now = now() - self.shift;
+        while now > next + interval {
+            next += interval;
+        }
+        if now < next {
+            let lag = u64::try_from(next - now).unwrap();
+            if sleep(std::time::Duration::from_secs(lag), interrupted) {
+                return Ok(Next::Interrupted);
+            }
+        }
+        self.to = next;
+        let fetch = self.query.fetch(self.rows_to - interval, self.to)?;
+        notices.extend(fetch.notices);
+        Ok(match fetch.data {
+            Ok(Some(fetched)) => {
+                self.rows(self.to, &fetched.times, &fetched.vals, fetched.ncurrent);
+                Next::Rows {
+                    times: fetched.times,
+                    vals: fetched.vals,
+                    ncurrent: fetched.ncurrent,
+                }
+            }
+            Ok(None) => Next::NoData,
+            Err(err) => Next::Failed(err),
+        })
+    }
+
+    // waits for a publication in a later interval than the last row, which
+    // becomes the next row. None when interrupted
+    fn push(&mut self, interrupted: &dyn Fn() -> bool) -> Result<Option<Next>, String> {
+        let live = self.live.as_mut().unwrap();
+        loop {
+            loop {
+                if interrupted() {
+                    return Ok(None);
+                }
+                if live.ready(std::time::Duration::from_millis(100))? {
+                    break;
+                }
+            }
+            let (channel, frame) = live.next_push()?;
+            let Some(k) = (self.channels.iter()).position(|c| c.as_deref() == Some(&channel))
+            else {
+                continue;
+            };
+            let Ok(frame) = decode::<Frame>(&frame) else {
+                continue;
+            };
+            let Some(&t) = frame.times().last() else {
+                continue;
+            };
+            if t < self.time + self.query.interval * 1000 {
+                continue;
+            }
+            let (row, vals) = push_row(&self.last, k, &frame.series());
+            let times = vec![self.time, t];
+            self.to = t / 1000;
+            self.rows_to = self.to;
+            self.time = t;
+            self.last = row;
+            return Ok(Some(Next::Rows {
+                times,
+                vals,
+                ncurrent: self.ncurrent,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn push_row() {
+        let last = vec![vec![Some(1.0), Some(2.0)], vec![Some(3.0)]];
+        let series = vec![vec![Some(5.0), Some(6.0)], vec![None]];
+        let (row, vals) = super::push_row(&last, 0, &series);
+        assert_eq!(row, vec![vec![Some(6.0), None], vec![Some(3.0)]]);
+        assert_eq!(
+            vals,
+            vec![
+                vec![vec![Some(1.0), Some(6.0)], vec![Some(2.0), None]],
+                vec![vec![Some(3.0), Some(3.0)]],
+            ]
+        );
+        // a frame with fewer series than before leaves the rest empty
+        let (row, _) = super::push_row(&last, 1, &[]);
+        assert_eq!(row[1], vec![None]);
+    }
+}
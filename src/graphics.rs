@@ -0,0 +1,201 @@
+// images inline in the terminal, as sixels or with kitty's graphics protocol.
+// which one a terminal shows is up to the caller to find out.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::base64;
+use crate::raster::Image;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+}
+
+/// writes `image` at the cursor, which ends up on the image's last line
+pub fn write(w: &mut impl Write, protocol: Protocol, image: &Image) -> std::io::Result<()> {
+    match protocol {
+        Protocol::Sixel => sixel(w, image)?,
+        Protocol::Kitty => kitty(w, image)?,
+    }
+    w.flush()
+}
+
+// https://sw.kovidgoyal.net/kitty/graphics-protocol/, the raw pixels in
+// base64 chunks of at most 4096 bytes
+fn kitty(w: &mut impl Write, image: &Image) -> std::io::Result<()> {
+    let data = base64::encode(&image.rgb);
+    let chunks: Vec<_> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            // q=2 so the terminal doesn't answer on stdin
+            let (width, height) = (image.width, image.height);
+            write!(w, "\x1b_Ga=T,q=2,f=24,s={width},v={height},m={more};")?;
+        } else {
+            write!(w, "\x1b_Gm={more};")?;
+        }
+        w.write_all(chunk)?;
+        w.write_all(b"\x1b\\")?;
+    }
+    Ok(())
+}
+
+// sixels have a palette of at most 256 colors: the image's most common ones,
+// other pixels get the nearest of them. returns the palette and the index of
+// each pixel's color in it
+fn quantize(image: &Image) -> (Vec<u32>, Vec<u8>) {
+    let pixels: Vec<u32> = image
+        .rgb
+        .chunks(3)
+        .map(|p| u32::from(p[0]) << 16 | u32::from(p[1]) << 8 | u32::from(p[2]))
+        .collect();
+    let mut counts = HashMap::new();
+    for &rgb in &pixels {
+        *counts.entry(rgb).or_insert(0) += 1;
+    }
+    let mut colors: Vec<(u32, usize)> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let palette: Vec<u32> = colors.iter().take(256).map(|&(rgb, _)| rgb).collect();
+    let mut nearest: HashMap<u32, u8> = (palette.iter().enumerate())
+        .map(|(i, &rgb)| (rgb, i as u8))
+        .collect();
+    let distance = |a: u32, b: u32| {
+        [16, 8, 0]
+            .into_iter()
+            .map(|shift| {
+                let d = i64::from(a >> shift & 0xff) - i64::from(b >> shift & 0xff);
+                d * d
+            })
+            .sum::<i64>()
+    };
+    let index = pixels
+        .iter()
+        .map(|&rgb| {
+            *nearest.entry(rgb).or_insert_with(|| {
+                (0..palette.len())
+                    .min_by_key(|&i| distance(rgb, palette[i]))
+                    .unwrap_or(0) as u8
+            })
+        })
+        .collect();
+    (palette, index)
+}
+
+// https://vt100.net/docs/vt3xx-gp/chapter14.html, bands of six pixel rows,
+// one pass over a band for each of its colors
+fn sixel(w: &mut impl Write, image: &Image) -> std::io::Result<()> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (palette, index) = quantize(image);
+    write!(w, "\x1bPq\"1;1;{width};{height}")?;
+    for (i, rgb) in palette.iter().enumerate() {
+        let percent = |shift: u32| (rgb >> shift & 0xff) * 100 / 255;
+        write!(w, "#{i};2;{};{};{}", percent(16), percent(8), percent(0))?;
+    }
+    let mut bands: Vec<Option<Vec<u8>>> = vec![None; palette.len()];
+    for top in (0..height).step_by(6) {
+        for (dy, row) in (top..height.min(top + 6)).enumerate() {
+            for x in 0..width {
+                let color = usize::from(index[row * width + x]);
+                bands[color].get_or_insert_with(|| vec![0; width])[x] |= 1 << dy;
+            }
+        }
+        for (color, band) in bands.iter_mut().enumerate() {
+            let Some(sixels) = band.take() else {
+                continue;
+            };
+            write!(w, "#{color}")?;
+            // runs of the same sixel are repeated with !COUNT
+            let mut x = 0;
+            while x < width {
+                let run = sixels[x..].iter().take_while(|&&s| s == sixels[x]).count();
+                let c = char::from(0x3f + sixels[x]);
+                if run > 3 {
+                    write!(w, "!{run}{c}")?;
+                } else {
+                    write!(w, "{}", c.to_string().repeat(run))?;
+                }
+                x += run;
+            }
+            // back to the start of the band for the next color
+            w.write_all(b"$")?;
+        }
+        w.write_all(b"-")?;
+    }
+    w.write_all(b"\x1b\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, pixels: &[u32]) -> Image {
+        Image {
+            width,
+            height,
+            rgb: (pixels.iter())
+                .flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8])
+                .collect(),
+        }
+    }
+
+    fn written(protocol: Protocol, image: &Image) -> String {
+        let mut out = vec![];
+        write(&mut out, protocol, image).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn kitty_chunks() {
+        // 6000 bytes of pixels are 8000 of base64
+        let out = written(Protocol::Kitty, &image(50, 40, &[0x102030; 2000]));
+        let chunks: Vec<_> = out.split_terminator("\x1b\\").collect();
+        assert_eq!(chunks.len(), 2);
+        let first = chunks[0]
+            .strip_prefix("\x1b_Ga=T,q=2,f=24,s=50,v=40,m=1;")
+            .unwrap();
+        let last = chunks[1].strip_prefix("\x1b_Gm=0;").unwrap();
+        assert_eq!((first.len(), last.len()), (4096, 8000 - 4096));
+        assert_eq!(first[..4], *"ECAw");
+
+        // exactly one chunk's worth
+        let out = written(Protocol::Kitty, &image(32, 32, &[0; 1024]));
+        let chunks: Vec<_> = out.split_terminator("\x1b\\").collect();
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].starts_with("\x1b_Ga=T,q=2,f=24,s=32,v=32,m=0;"));
+        assert_eq!(chunks[0].len() - chunks[0].find(';').unwrap() - 1, 4096);
+    }
+
+    #[test]
+    fn sixel_runs() {
+        let (w, k) = (0xffffff, 0x000000);
+        // the more common white is register 0
+        let out = written(
+            Protocol::Sixel,
+            &image(5, 2, &[w, w, w, w, w, w, k, k, k, k]),
+        );
+        assert_eq!(
+            out,
+            "\x1bPq\"1;1;5;2#0;2;100;100;100#1;2;0;0;0#0B!4@$#1?!4A$-\x1b\\"
+        );
+        // three of a kind are shorter written out, and a second band starts
+        // at the seventh row
+        let red = 0xff0000;
+        let out = written(Protocol::Sixel, &image(3, 7, &[red; 21]));
+        assert_eq!(out, "\x1bPq\"1;1;3;7#0;2;100;0;0#0~~~$-#0@@@$-\x1b\\");
+    }
+
+    #[test]
+    fn sixel_palette() {
+        // past 256 colors the least common get the nearest register
+        let mut pixels: Vec<u32> = (0..256).flat_map(|c| [c << 8; 2]).collect();
+        pixels.push(0x0001ff);
+        let (palette, index) = quantize(&image(513, 1, &pixels));
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette[usize::from(index[2])], 0x000100);
+        // of all the greens, 0x0001ff is nearest the same one
+        assert!(!palette.contains(&0x0001ff));
+        assert_eq!(index[512], index[2]);
+    }
+}
@@ -0,0 +1,30 @@
+//! graf talks to grafana for you: it looks up dashboards and panels, runs
+//! their queries through grafana's datasource proxy and draws what comes back
+//! as a text chart of ascii lines (`-`, `|`, `.` and `'`), or follows it over
+//! grafana live.
+//!
+//! [`client::Client`] makes the requests, and [`models`] has the dashboards
+//! and query results they return. [`query`] builds and runs panel queries,
+//! and [`time`] parses grafana's time ranges for them. [`follow::Follow`]
+//! keeps running one as time goes on, or has grafana live push its rows.
+//!
+//! what comes back is drawn by [`render::Chart`] as text, or written out for
+//! other tools by [`table::Table`]. a [`plot::Plot`] of it is drawn as an
+//! [`svg`], or in pixels by [`raster`] for terminals that show [`graphics`].
+
+pub mod auth;
+mod base64;
+pub mod client;
+pub mod color;
+pub mod follow;
+pub mod graphics;
+pub mod live;
+pub mod models;
+pub mod plot;
+pub mod query;
+pub mod raster;
+pub mod render;
+pub mod svg;
+pub mod table;
+pub mod time;
+pub mod tz;
@@ -0,0 +1,713 @@
+// grafana live is centrifuge's json protocol over a websocket at /api/live/ws
+// https://centrifugal.dev/docs/transports/client_protocol
+// plain http connects directly, https goes through `openssl s_client` the
+// same way everything else goes through curl.
+
+use std::io::{BufRead as _, BufReader, Read, Write};
+use std::os::fd::AsRawFd as _;
+
+use crate::client::Tls;
+
+trait Stream: Read + Write {
+    // what went wrong once the stream was closed on us
+    fn error(&mut self) -> Option<String> {
+        None
+    }
+
+    // the descriptor reads come from, to wait on
+    fn fd(&self) -> std::os::fd::RawFd;
+}
+
+impl Stream for std::net::TcpStream {
+    fn fd(&self) -> std::os::fd::RawFd {
+        self.as_raw_fd()
+    }
+}
+
+// joins a child's stdin and stdout into one stream
+struct Pipe(std::process::Child);
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.stdout.as_mut().unwrap().read(buf)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.stdin.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.stdin.as_mut().unwrap().flush()
+    }
+}
+
+impl Stream for Pipe {
+    fn fd(&self) -> std::os::fd::RawFd {
+        self.0.stdout.as_ref().unwrap().as_raw_fd()
+    }
+
+    fn error(&mut self) -> Option<String> {
+        let mut stderr = String::new();
+        self.0.stderr.take()?.read_to_string(&mut stderr).ok()?;
+        // openssl says "verify error:num=20:unable to get local issuer
+        // certificate", or "...:SSL routines:ssl3_read_bytes:tlsv13 alert
+        // certificate required:ssl/record/rec_layer_s3.c:918:..."
+        let lines = || stderr.lines().map(str::trim);
+        let (message, hint) = if let Some(l) = lines().find(|l| l.starts_with("verify error")) {
+            let message = l.splitn(3, ':').nth(2).unwrap_or(l);
+            let hint = if message.contains("mismatch") {
+                "grafana's certificate is for another host, use that name in --url"
+            } else {
+                "give the CA that signed grafana's certificate with --cacert (or skip verification with --insecure)"
+            };
+            (message, hint)
+        } else if let Some(l) = lines().find(|l| l.contains("alert")) {
+            (
+                l.split(':').find(|f| f.contains("alert")).unwrap_or(l),
+                "grafana may want a client certificate (--cert and --key)",
+            )
+        } else {
+            (
+                lines().find(|l| !l.is_empty() && !l.starts_with("Connecting"))?,
+                "check the --cacert, --cert and --key files",
+            )
+        };
+        Some(format!("tls handshake failed: {message}, {hint}"))
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+pub struct Live {
+    stream: BufReader<Box<dyn Stream>>,
+    next_id: u64,
+    // replies to pings with pongs
+    pong: bool,
+    // messages read while waiting on a reply
+    pending: std::collections::VecDeque<serde_json::Value>,
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut buf = [0; N];
+    if std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .is_err()
+    {
+        // masking is only there to confuse proxies, any value will do
+        let t = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = (t >> (i % 16 * 8)) as u8;
+        }
+    }
+    buf
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend((data.len() as u64 * 8).to_be_bytes());
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = (a.rotate_left(5))
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(wi);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0; 20];
+    for (o, h) in out.chunks_mut(4).zip(h) {
+        o.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+// the Sec-WebSocket-Accept a server that understood the upgrade for `key`
+// answers with
+fn accept(key: &str) -> String {
+    let guid = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    crate::base64::encode(&sha1(format!("{key}{guid}").as_bytes()))
+}
+
+const OP_CONT: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+/// writes a single final websocket frame, masked as clients must
+pub(crate) fn write_frame(
+    w: &mut impl Write,
+    opcode: u8,
+    payload: &[u8],
+    mask: bool,
+) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let mask_bit = if mask { 0x80 } else { 0 };
+    match payload.len() {
+        n @ 0..=125 => frame.push(mask_bit | n as u8),
+        n @ 126..=0xffff => {
+            frame.push(mask_bit | 126);
+            frame.extend((n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(mask_bit | 127);
+            frame.extend((n as u64).to_be_bytes());
+        }
+    }
+    if mask {
+        let key: [u8; 4] = random_bytes();
+        frame.extend(key);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+    } else {
+        frame.extend(payload);
+    }
+    w.write_all(&frame)?;
+    w.flush()
+}
+
+// far larger than anything grafana pushes, but a corrupt or hostile length
+// shouldn't have us allocate whatever it says
+const MAX_MESSAGE: u64 = 64 << 20;
+
+/// reads a single websocket frame, returning (fin, opcode, payload)
+pub(crate) fn read_frame(r: &mut impl Read) -> std::io::Result<(bool, u8, Vec<u8>)> {
+    let mut hdr = [0; 2];
+    r.read_exact(&mut hdr)?;
+    let (fin, opcode, masked) = (hdr[0] & 0x80 != 0, hdr[0] & 0x0f, hdr[1] & 0x80 != 0);
+    let len = match hdr[1] & 0x7f {
+        126 => {
+            let mut n = [0; 2];
+            r.read_exact(&mut n)?;
+            u64::from(u16::from_be_bytes(n))
+        }
+        127 => {
+            let mut n = [0; 8];
+            r.read_exact(&mut n)?;
+            u64::from_be_bytes(n)
+        }
+        n => u64::from(n),
+    };
+    if len > MAX_MESSAGE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes, more than the {MAX_MESSAGE} allowed"),
+        ));
+    }
+    let mut key = [0; 4];
+    if masked {
+        r.read_exact(&mut key)?;
+    }
+    let mut payload = vec![0; len as usize];
+    r.read_exact(&mut payload)?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= key[i % 4];
+        }
+    }
+    Ok((fin, opcode, payload))
+}
+
+impl Live {
+    /// connects to grafana live under `url`, sending `headers` (like
+    /// 'Authorization: Bearer ...') with the websocket upgrade
+    pub fn connect(url: &str, headers: &[String], tls: &Tls) -> Result<Live, String> {
+        let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(format!("unsupported url {url:?}"));
+        };
+        let (authority, base) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+        let addr = if host
+            .rsplit_once(':')
+            .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+        {
+            host.to_string()
+        } else if https {
+            format!("{host}:443")
+        } else {
+            format!("{host}:80")
+        };
+
+        let stream: Box<dyn Stream> = if https {
+            let servername = host.rsplit_once(':').map_or(host, |(h, _)| h);
+            let child = std::process::Command::new("openssl")
+                .args(["s_client", "-quiet"])
+                .args(tls.openssl_args(servername))
+                .args(["-connect", &addr, "-servername", servername])
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .map_err(|err| format!("unable to run openssl for tls: {err}"))?;
+            Box::new(Pipe(child))
+        } else {
+            let stream = std::net::TcpStream::connect(&addr)
+                .map_err(|err| format!("unable to connect to {addr}: {err}"))?;
+            Box::new(stream)
+        };
+        let mut stream = BufReader::new(stream);
+
+        let key = crate::base64::encode(&random_bytes::<16>());
+        let base = base.trim_end_matches('/');
+        let mut req = format!(
+            "GET {base}/api/live/ws HTTP/1.1\r\n\
+             Host: {host}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {key}\r\n\
+             Sec-WebSocket-Version: 13\r\n"
+        );
+        for h in headers {
+            req.push_str(h);
+            req.push_str("\r\n");
+        }
+        req.push_str("\r\n");
+        let ioerr = |err: std::io::Error| format!("grafana live: {err}");
+        let mut status = String::new();
+        let sent = stream
+            .get_mut()
+            .write_all(req.as_bytes())
+            .and_then(|()| stream.get_mut().flush())
+            .and_then(|()| stream.read_line(&mut status));
+        if sent.is_err() || status.is_empty() {
+            // openssl exits when the handshake fails, and says why
+            if let Some(err) = stream.get_mut().error() {
+                return Err(format!("grafana live: {err}"));
+            }
+            sent.map_err(ioerr)?;
+            return Err("grafana live: connection closed".to_string());
+        }
+        let mut accepted = None;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).map_err(ioerr)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("sec-websocket-accept") {
+                    accepted = Some(value.trim().to_string());
+                }
+            }
+        }
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(format!("grafana live upgrade refused: {}", status.trim()));
+        }
+        if accepted.as_deref() != Some(&accept(&key)) {
+            return Err(
+                "grafana live upgrade not accepted: Sec-WebSocket-Accept doesn't match the key"
+                    .to_string(),
+            );
+        }
+
+        let mut live = Live {
+            stream,
+            next_id: 1,
+            pong: false,
+            pending: Default::default(),
+        };
+        let reply = live.command("connect", serde_json::json!({}))?;
+        live.pong = reply["pong"].as_bool().unwrap_or(false);
+        Ok(live)
+    }
+
+    fn send(&mut self, msg: &serde_json::Value) -> Result<(), String> {
+        write_frame(
+            self.stream.get_mut(),
+            OP_TEXT,
+            msg.to_string().as_bytes(),
+            true,
+        )
+        .map_err(|err| format!("grafana live: {err}"))
+    }
+
+    // reads the next batch of messages, answering pings along the way
+    fn recv(&mut self) -> Result<Vec<serde_json::Value>, String> {
+        let ioerr = |err: std::io::Error| format!("grafana live: {err}");
+        let mut message = vec![];
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut self.stream).map_err(ioerr)?;
+            match opcode {
+                OP_TEXT | OP_BINARY | OP_CONT => {
+                    message.extend(payload);
+                    if message.len() as u64 > MAX_MESSAGE {
+                        return Err(format!(
+                            "grafana live: message of more than {MAX_MESSAGE} bytes"
+                        ));
+                    }
+                }
+                OP_PING => {
+                    write_frame(self.stream.get_mut(), OP_PONG, &payload, true).map_err(ioerr)?
+                }
+                OP_PONG => {}
+                OP_CLOSE => return Err("grafana live: connection closed".to_string()),
+                op => return Err(format!("grafana live: unexpected opcode {op}")),
+            }
+            if fin && !message.is_empty() {
+                break;
+            }
+        }
+
+        // several replies may be batched into one message, one per line
+        let mut msgs = vec![];
+        for line in message.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let msg: serde_json::Value = serde_json::from_slice(line)
+                .map_err(|err| format!("grafana live: invalid message: {err}"))?;
+            // an empty message is a ping from the server
+            if msg.as_object().is_some_and(|m| m.is_empty()) {
+                if self.pong {
+                    self.send(&serde_json::json!({}))?;
+                }
+                continue;
+            }
+            msgs.push(msg);
+        }
+        Ok(msgs)
+    }
+
+    fn command(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&serde_json::json!({ "id": id, method: params }))?;
+        let reply = loop {
+            let msgs = self.recv()?;
+            self.pending.extend(msgs);
+            if let Some(k) = self
+                .pending
+                .iter()
+                .position(|m| m["id"].as_u64() == Some(id))
+            {
+                break self.pending.remove(k).unwrap();
+            }
+        };
+        if let Some(err) = reply.get("error") {
+            return Err(format!(
+                "grafana live {method}: {} ({})",
+                err["message"].as_str().unwrap_or("error"),
+                err["code"]
+            ));
+        }
+        // protocol v1 wraps every reply in "result"
+        Ok(reply
+            .get(method)
+            .or_else(|| reply.get("result"))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    pub fn subscribe(&mut self, channel: &str) -> Result<(), String> {
+        self.command("subscribe", serde_json::json!({ "channel": channel }))
+            .map(|_| ())
+    }
+
+    /// whether there's something to read, waiting up to `timeout` for it.
+    /// a signal ends the wait early
+    pub fn ready(&mut self, timeout: std::time::Duration) -> Result<bool, String> {
+        if !self.pending.is_empty() || !self.stream.buffer().is_empty() {
+            return Ok(true);
+        }
+        let mut fd = libc::pollfd {
+            fd: self.stream.get_ref().fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        // SAFETY: ffi, with the one pollfd it's given
+        match unsafe { libc::poll(&mut fd, 1, timeout) } {
+            -1 => match std::io::Error::last_os_error() {
+                err if err.kind() == std::io::ErrorKind::Interrupted => Ok(false),
+                err => Err(format!("grafana live: {err}")),
+            },
+            n => Ok(n > 0),
+        }
+    }
+
+    /// blocks until the next publication, returning its channel and data
+    pub fn next_push(&mut self) -> Result<(String, serde_json::Value), String> {
+        loop {
+            let msg = match self.pending.pop_front() {
+                Some(msg) => msg,
+                None => {
+                    let msgs = self.recv()?;
+                    self.pending.extend(msgs);
+                    continue;
+                }
+            };
+            let (channel, data) = if let Some(push) = msg.get("push") {
+                (&push["channel"], &push["pub"]["data"])
+            } else if let Some(result) = msg.get("result").filter(|_| msg.get("id").is_none()) {
+                (&result["channel"], &result["data"]["data"])
+            } else {
+                continue;
+            };
+            if let (Some(channel), false) = (channel.as_str(), data.is_null()) {
+                return Ok((channel.to_string(), data.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a grafana live stand-in that answers connect and subscribe, then pushes
+    // one frame on the subscribed channel after pinging the client
+    fn serve(listener: std::net::TcpListener) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = BufReader::new(stream);
+        let mut req = vec![];
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            req.push(line.trim().to_string());
+        }
+        assert!(req[0].starts_with("GET /grafana/api/live/ws "), "{req:?}");
+        assert!(
+            req.contains(&"Authorization: Bearer tok".to_string()),
+            "{req:?}"
+        );
+        let key = req
+            .iter()
+            .find_map(|h| h.strip_prefix("Sec-WebSocket-Key: "))
+            .unwrap();
+        let upgrade = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept(key)
+        );
+        stream.get_mut().write_all(upgrade.as_bytes()).unwrap();
+
+        let recv = |stream: &mut BufReader<std::net::TcpStream>| {
+            let (_, opcode, payload) = read_frame(stream).unwrap();
+            assert_eq!(opcode, OP_TEXT);
+            serde_json::from_slice::<serde_json::Value>(&payload).unwrap()
+        };
+        let connect = recv(&mut stream);
+        assert!(connect.get("connect").is_some(), "{connect}");
+        let reply =
+            serde_json::json!({"id": connect["id"], "connect": {"client": "x", "pong": true}});
+        write_frame(
+            stream.get_mut(),
+            OP_TEXT,
+            reply.to_string().as_bytes(),
+            false,
+        )
+        .unwrap();
+
+        let sub = recv(&mut stream);
+        assert_eq!(sub["subscribe"]["channel"], "ds/abc/stream");
+        // ping before replying, the client should pong while waiting
+        write_frame(stream.get_mut(), OP_TEXT, b"{}", false).unwrap();
+        let pong = recv(&mut stream);
+        assert_eq!(pong, serde_json::json!({}));
+
+        // reply and push batched into one message
+        let reply = serde_json::json!({"id": sub["id"], "subscribe": {}});
+        let push = serde_json::json!({"push": {"channel": "ds/abc/stream", "pub": {"data": {
+            "data": {"values": [[1000, 2000], [1.5, 2.5]]}
+        }}}});
+        let batch = format!("{reply}\n{push}");
+        write_frame(stream.get_mut(), OP_TEXT, batch.as_bytes(), false).unwrap();
+    }
+
+    #[test]
+    fn live_subscribe_push() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || serve(listener));
+
+        let url = format!("http://{addr}/grafana/");
+        let mut live = Live::connect(
+            &url,
+            &["Authorization: Bearer tok".to_string()],
+            &Tls::default(),
+        )
+        .unwrap();
+        assert!(live.pong);
+        live.subscribe("ds/abc/stream").unwrap();
+        let (channel, data) = live.next_push().unwrap();
+        assert_eq!(channel, "ds/abc/stream");
+        assert_eq!(data["data"]["values"][1], serde_json::json!([1.5, 2.5]));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn upgrade_not_accepted() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            while stream.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            // a server that doesn't speak websockets but says it does
+            let upgrade = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept("another key")
+            );
+            stream.get_mut().write_all(upgrade.as_bytes()).unwrap();
+        });
+        let url = format!("http://{addr}/");
+        let err = Live::connect(&url, &[], &Tls::default()).err().unwrap();
+        assert!(err.contains("Sec-WebSocket-Accept doesn't match"), "{err}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn accept_key() {
+        // the example in RFC 6455
+        assert_eq!(
+            accept("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        let hex = |d: [u8; 20]| d.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        // more than one block
+        assert_eq!(
+            hex(sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+
+    // an `openssl s_server` with a certificate for `name`, and the
+    // directory with the certificate to trust it with. None if there's no
+    // openssl to run
+    fn tls_server(name: &str) -> Option<(std::process::Child, u16, std::path::PathBuf)> {
+        let dir = std::env::temp_dir().join(format!("graf-live-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let status = std::process::Command::new("openssl")
+            .args(["req", "-x509", "-newkey", "ec", "-pkeyopt"])
+            .args(["ec_paramgen_curve:prime256v1", "-nodes", "-days", "1"])
+            .args(["-subj", &format!("/CN={name}")])
+            .args(["-addext", &format!("subjectAltName=DNS:{name}")])
+            .arg("-keyout")
+            .arg(&key)
+            .arg("-out")
+            .arg(&cert)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .ok()?;
+        assert!(status.success());
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = std::process::Command::new("openssl")
+            .args(["s_server", "-quiet", "-www", "-accept"])
+            .arg(port.to_string())
+            .arg("-cert")
+            .arg(&cert)
+            .arg("-key")
+            .arg(&key)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        for _ in 0..100 {
+            if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        Some((server, port, dir))
+    }
+
+    #[test]
+    fn live_tls_hostname() {
+        let Some((mut server, port, dir)) = tls_server("grafana.invalid") else {
+            eprintln!("skipping, no openssl");
+            return;
+        };
+        let tls = Tls {
+            cacert: Some(dir.join("cert.pem").to_string_lossy().to_string()),
+            ..Tls::default()
+        };
+        // a certificate signed by a trusted CA, but for another host
+        let url = format!("https://localhost:{port}/");
+        let for_another_host = "grafana's certificate is for another host";
+        let err = Live::connect(&url, &[], &tls).err().unwrap();
+        assert!(
+            err.starts_with("grafana live: tls handshake failed: "),
+            "{err}"
+        );
+        assert!(err.contains(for_another_host), "{err}");
+        let url = format!("https://127.0.0.1:{port}/");
+        let err = Live::connect(&url, &[], &tls).err().unwrap();
+        assert!(err.contains(for_another_host), "{err}");
+        // without verification the handshake goes through, and s_server
+        // answers the upgrade with a web page
+        let insecure = Tls {
+            insecure: true,
+            ..Tls::default()
+        };
+        let err = Live::connect(&url, &[], &insecure).err().unwrap();
+        assert!(err.contains("upgrade refused"), "{err}");
+        server.kill().unwrap();
+        server.wait().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn frame_too_large() {
+        let mut frame = vec![0x81, 127];
+        frame.extend(u64::MAX.to_be_bytes());
+        let err = read_frame(&mut &frame[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // a frame within the limit is still read whole
+        let mut frame = vec![];
+        write_frame(&mut frame, OP_TEXT, &[b'x'; 70000], true).unwrap();
+        let (fin, opcode, payload) = read_frame(&mut &frame[..]).unwrap();
+        assert!(fin);
+        assert_eq!(opcode, OP_TEXT);
+        assert_eq!(payload, [b'x'; 70000]);
+    }
+}
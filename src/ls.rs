@@ -0,0 +1,61 @@
+// graf ls, what there is on grafana. each is a line of tab separated fields,
+// the uid (or index for panels) first, so the output can be cut and grepped.
+
+use std::io::Write;
+
+use graf::client::Client;
+
+use crate::{ok, piped, Dashboards, Error};
+
+pub fn run(
+    client: &Client,
+    dashboards: &Dashboards,
+    args: &[String],
+    debug: usize,
+) -> Result<(), Error> {
+    let lines: Vec<String> = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["dashboards"] => (dashboards.list(client).iter())
+            .map(|d| format!("{}\t{}\t{}", d.uid, d.folder_title, d.title))
+            .collect(),
+        ["panels", ref dash @ ..] if dash.len() <= 1 => {
+            let dash = dashboards.get(client, dash.first().copied(), debug);
+            (dash.queried_panels().iter().enumerate())
+                .map(|(k, p)| format!("{k}\t{}\t{}", p.kind, p.title))
+                .collect()
+        }
+        ["datasources"] => (ok(client.datasources()).iter())
+            .map(|d| format!("{}\t{}\t{}", d.uid, d.kind, d.name))
+            .collect(),
+        ["folders"] => (ok(client.folders()).iter())
+            .map(|f| format!("{}\t{}", f.uid, f.title))
+            .collect(),
+        _ => {
+            eprintln!("error: ls lists dashboards, panels [DASH], datasources or folders");
+            crate::usage(Some(crate::Command::Ls), true);
+            return Err(Error::quiet(1));
+        }
+    };
+    piped(write_lines(&mut std::io::stdout().lock(), &lines))
+}
+
+fn write_lines(w: &mut impl Write, lines: &[String]) -> std::io::Result<()> {
+    for line in lines {
+        writeln!(w, "{line}")?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closed_reader() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        drop(reader);
+        let lines = vec!["uid\tfolder\ttitle".to_string()];
+        let err = piped(write_lines(&mut writer, &lines)).unwrap_err();
+        assert_eq!(err.code, 0);
+        assert!(err.message.is_none());
+    }
+}
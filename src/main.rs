@@ -1,170 +1,393 @@
-use std::io::Write as _;
+mod config;
+mod ls;
+mod panel;
+mod pick;
+mod term;
+mod tui;
+
+use graf::auth::{self, Auth, Session};
+use graf::client::{Client, Failure, Search, Tls};
+use graf::color;
+use graf::graphics::Protocol;
+use graf::models::{Dashboard, SearchHit};
+use graf::table::Format;
+use graf::time::parse_duration;
+use graf::tz::Tz;
+
+static RESIZED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, std::sync::atomic::Ordering::Relaxed);
+}
 
-#[derive(Clone)]
-#[repr(transparent)]
-struct Value(serde_json::Value);
+/// notes terminal resizes from now on, for `resized` to pick up
+fn watch_resize() {
+    // SAFETY: ffi, the handler only stores to an atomic
+    unsafe { libc::signal(libc::SIGWINCH, on_resize as *const () as libc::sighandler_t) };
+}
 
-impl std::fmt::Debug for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        <serde_json::Value as std::fmt::Debug>::fmt(&self.0, f)
-    }
+/// whether the terminal was resized since the last call
+fn resized() -> bool {
+    RESIZED.swap(false, std::sync::atomic::Ordering::Relaxed)
 }
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        <serde_json::Value as std::fmt::Display>::fmt(&self.0, f)
-    }
+
+/// whether there's a resize for `resized` to pick up, leaving it there
+fn resize_pending() -> bool {
+    RESIZED.load(std::sync::atomic::Ordering::Relaxed)
 }
 
-impl Value {
-    #[track_caller]
-    fn i(&self) -> i64 {
-        self.0.as_i64().unwrap()
-    }
-    #[track_caller]
-    fn s(&self) -> &str {
-        self.0.as_str().unwrap()
-    }
-    #[track_caller]
-    fn f(&self) -> f64 {
-        self.0.as_f64().unwrap()
+/// prints the failure and exits with its code, see the usage text
+fn fail(failure: &Failure) -> ! {
+    eprintln!("error: {failure}");
+    std::process::exit(failure.code())
+}
+
+/// the ok value, or exits for the failure
+fn ok<T>(res: Result<T, Failure>) -> T {
+    res.unwrap_or_else(|failure| fail(&failure))
+}
+
+/// why a command stopped early, and the code to exit with
+struct Error {
+    code: i32,
+    // printed as "error: ...", a quiet exit has none
+    message: Option<String>,
+}
+
+impl Error {
+    fn new(code: i32, message: impl Into<String>) -> Error {
+        Error {
+            code,
+            message: Some(message.into()),
+        }
     }
-    #[track_caller]
-    fn a(&self) -> &[Value] {
-        let a = self.0.as_array().unwrap().as_slice();
-        unsafe { std::mem::transmute(a) }
+
+    fn quiet(code: i32) -> Error {
+        Error {
+            code,
+            message: None,
+        }
     }
 }
 
-impl<K: serde_json::value::Index> std::ops::Index<K> for Value {
-    type Output = Value;
-
-    fn index(&self, index: K) -> &Self::Output {
-        let v = &self.0[index];
-        unsafe { std::mem::transmute(v) }
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Error {
+        Error::new(failure.code(), failure.to_string())
     }
 }
 
-fn usage(short: bool) {
-    println!(
-        "usage: graf [-h|--help] <-u USER:PASS|-t TOKEN> URL [--from FROM] [--to TO] [--interval SECS] [-f] [-d DASHBOARD] [-p PANEL]"
-    );
-    if short {
-        return;
+/// prints the error, unless it's a quiet one, and exits with its code
+fn exit(err: Error) -> ! {
+    if let Some(message) = err.message {
+        eprintln!("error: {message}");
     }
-    print!(
-        r#"  select and print grafana dashboard panel to terminal
+    std::process::exit(err.code)
+}
 
-  -u USER:PASS basic user password authentication
-  -t TOKEN     api token
-  URL          grafana base url
-  --from FROM, --to TO
-               time specifiers for grafana (defaults to now-1m, now)
-  INTERVAL    interval in seconds between frames (defaults to <terminal rows> / TO-FROM)
-  -f           follow, update data every INTERVAL seconds
-
-{} {} by {}"#,
-        env!("CARGO_PKG_NAME"),
-        env!("CARGO_PKG_VERSION"),
-        env!("CARGO_PKG_AUTHORS")
-    );
+// the tool reading the output may have had enough, like head
+fn piped(res: std::io::Result<()>) -> Result<(), Error> {
+    res.map_err(|err| match err.kind() {
+        std::io::ErrorKind::BrokenPipe => Error::quiet(0),
+        _ => Error::new(1, format!("unable to write output: {err}")),
+    })
 }
 
-// https://github.com/shua/graf/issues/1 libc for macos doesn't define these
-// https://github.com/rust-lang/libc/pull/3152
-#[cfg(target_os = "macos")]
-mod libc {
-    #![allow(bad_style)]
-    pub use libc::*;
-
-    extern "C" {
-        pub fn strftime(
-            s: *mut c_char,
-            max: size_t,
-            format: *const c_char,
-            tm: *const tm,
-        ) -> size_t;
-    }
+/// rows and columns to draw in, `width` and `height` win over the terminal's
+/// size, then $COLUMNS and $LINES for when stdout isn't a terminal
+fn winsize(width: Option<u16>, height: Option<u16>) -> (u16, u16) {
+    let mut winsz = libc::winsize {
+        ws_col: 0,
+        ws_row: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: just ffi
+    let ret = unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut winsz as *mut _) };
+    let env = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse::<u16>().ok())
+            .filter(|&v| v > 0)
+    };
+    let tty = |v: u16| Some(v).filter(|&v| ret == 0 && v > 0);
+    let rows = height
+        .or_else(|| tty(winsz.ws_row))
+        .or_else(|| env("LINES"))
+        .unwrap_or(24);
+    let cols = width
+        .or_else(|| tty(winsz.ws_col))
+        .or_else(|| env("COLUMNS"))
+        .unwrap_or(80);
+    // time stamps take 9 columns, and the value scale wants some room after
+    (rows.max(2), cols.max(25))
 }
 
-pub fn timestamp<'b>(time_ms: i64, buf: &'b mut [u8; 9]) -> &'b str {
-    let time = libc::time_t::from(time_ms / 1000);
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Ls,
+    Show,
+    Render,
+    Watch,
+    Query,
+}
 
-    // SAFETY: C ffi stuff
-    unsafe {
-        let mut tm_buf = std::mem::zeroed::<libc::tm>();
-        let tm_ptr = (&mut tm_buf) as *mut _;
-        assert_eq!(libc::gmtime_r(&time, tm_ptr), tm_ptr, "gmtime(_, p) != p");
+// flags only some commands take, the rest go with every command
+const SEARCH_FLAGS: [&str; 5] = ["--query", "--tag", "--folder", "--starred", "--sort"];
+const RANGE_FLAGS: [&str; 3] = ["--from", "--to", "--interval"];
+const DRAW_FLAGS: [&str; 6] = [
+    "--width",
+    "--height",
+    "--color",
+    "--graphics",
+    "--tz",
+    "--compare",
+];
+
+impl Command {
+    const ALL: [Command; 5] = [
+        Command::Ls,
+        Command::Show,
+        Command::Render,
+        Command::Watch,
+        Command::Query,
+    ];
 
-        let (buf, max) = (buf.as_mut_ptr() as _, buf.len());
-        let date_fmt = "%H:%M:%S\0".as_ptr() as _;
-        let n = libc::strftime(buf, max, date_fmt, &tm_buf);
-        assert_eq!(n, 8, "stftime(_, _, \"%H:%M:%S\", _) != \"13:04:05\".len()");
+    fn name(self) -> &'static str {
+        match self {
+            Command::Ls => "ls",
+            Command::Show => "show",
+            Command::Render => "render",
+            Command::Watch => "watch",
+            Command::Query => "query",
+        }
     }
 
-    // SAFETY: asserted strftime wrote 8 bytes of valid ascii
-    unsafe { std::str::from_utf8_unchecked(&buf[..8]) }
-}
+    fn named(name: &str) -> Option<Command> {
+        Command::ALL.into_iter().find(|c| c.name() == name)
+    }
 
-pub fn parse_instant(time_s: &str, now: i64) -> Option<i64> {
-    let time_s = time_s.trim();
-    if time_s.chars().all(char::is_numeric) {
-        let time: i64 = time_s.parse().ok()?;
-        Some(time)
-    } else if time_s.len() == "20160201T130405".len()
-        && &time_s["20160201".len().."20160201T".len()] == "T"
-    {
-        let (year, time_s) = time_s.split_at("2016".len());
-        let (month, time_s) = time_s.split_at("02".len());
-        let (day, time_s) = time_s.split_at("01".len());
-        let (_t, time_s) = time_s.split_at("T".len());
-        let (hour, time_s) = time_s.split_at("13".len());
-        let (minute, time_s) = time_s.split_at("04".len());
-        let (second, _time_s) = time_s.split_at("05".len());
-        let mut tm = libc::tm {
-            tm_sec: second.parse().ok()?,
-            tm_min: minute.parse().ok()?,
-            tm_hour: hour.parse().ok()?,
-            tm_mday: day.parse().ok()?,
-            tm_mon: month.parse().ok()?,
-            tm_year: year.parse().ok()?,
-            tm_wday: 0,
-            tm_yday: 0,
-            tm_isdst: -1, // a negative value indicates mktime should use tz db to determine daylight savings
-            tm_gmtoff: 0,
-            tm_zone: "UTC".as_ptr() as _,
-        };
-        // SAFETY: ffi
-        let time = unsafe { libc::mktime(&mut tm as _) };
-        Some(time)
-    } else if time_s.len() >= "now".len() && &time_s[..3] == "now" {
-        let (_now, time_s) = time_s.split_at("now".len());
-        let time_s = time_s.trim_start();
-        match time_s.chars().next() {
-            None => Some(now),
-            Some(sig @ ('-' | '+')) => {
-                let sig = if sig == '-' { -1 } else { 1 };
-                let unit = match time_s.chars().last()? {
-                    's' => 1,
-                    'm' => 60,
-                    'h' => 60 * 60,
-                    'd' => 60 * 60 * 24,
-                    'y' => 60 * 60 * 24 * 365,
-                    _ => return None,
-                };
-                let n: i64 = time_s[1..(time_s.len() - 1)].trim_end().parse().ok()?;
-                Some(now + (sig * n * unit))
+    fn takes(self, flag: &str) -> bool {
+        let search = SEARCH_FLAGS.contains(&flag);
+        let range = RANGE_FLAGS.contains(&flag);
+        let draw = DRAW_FLAGS.contains(&flag);
+        let output = flag == "--output";
+        let svg = flag == "--svg";
+        match self {
+            Command::Ls => search,
+            Command::Query => search || range || output,
+            Command::Render => search || range || draw || output || svg,
+            Command::Watch => search || range || draw || output || flag == "--tui",
+            Command::Show => {
+                search || range || draw || output || svg || flag == "-f" || flag == "--tui"
+            }
+        }
+    }
+
+    fn synopsis(self) -> &'static str {
+        match self {
+            Command::Ls => "ls [SEARCH] dashboards|panels [DASH]|datasources|folders",
+            Command::Show => {
+                "show [RANGE] [DRAW] [SEARCH] [--output FORMAT] [--svg FILE] [-f] [--tui] [DASH [PANEL]]"
+            }
+            Command::Render => "render [RANGE] [DRAW] [SEARCH] [--output FORMAT] [--svg FILE] [DASH [PANEL]]",
+            Command::Watch => {
+                "watch [RANGE] [DRAW] [SEARCH] [--output FORMAT] [--tui] [DASH [PANEL]]"
+            }
+            Command::Query => "query [RANGE] [SEARCH] [--output FORMAT] [DASH [PANEL]]",
+        }
+    }
+
+    fn about(self) -> &'static str {
+        match self {
+            Command::Ls => {
+                "list dashboards (uid, folder, title), the panels of DASH (index,\n\
+                 type, title), datasources (uid, type, name) or folders (uid,\n\
+                 title), a line each with tab separated fields"
+            }
+            Command::Show => {
+                "draw PANEL of DASH, with -f keep drawing new rows as they come,\n\
+                 or browse the whole dashboard with --tui"
+            }
+            Command::Render => "draw PANEL of DASH once",
+            Command::Watch => {
+                "draw PANEL of DASH and keep drawing new rows as they come, like\n\
+                 show -f"
+            }
+            Command::Query => {
+                "print the frames of PANEL's query as json, as grafana returns\n\
+                 them, or its rows with --output"
             }
-            Some(_) => None,
         }
-    } else {
-        None
     }
 }
 
-fn main() {
-    // we always work in UTC here...
-    std::env::set_var("TZ", "UTC");
+const GLOBAL_SYNOPSIS: &str = "[-u USER:PASS|-t TOKEN|--token-file FILE|--credential-helper CMD|--login USER:PASS|--proxy-user USER] [--proxy-header NAME] [--cacert FILE] [--cert FILE] [--key FILE] [--insecure] [--url URL] [--org ORG] [--profile NAME] [-v]";
+
+const GLOBAL_HELP: &str = r#"  -u USER:PASS basic user password authentication, PASS - reads it from
+               stdin
+  -t TOKEN     api token, - reads it from stdin
+  --token-file FILE
+               read the api token from FILE
+  --credential-helper CMD
+               run the shell command CMD for the api token, like
+               'pass show grafana'
+               without any of these, the token comes from $GRAFANA_TOKEN, the
+               config profile, or the url's host in ~/.netrc. unlike -u and
+               -t with the secret in them, these don't leave credentials in
+               the process list
+  --login USER:PASS
+               log in with grafana's login form, the session cookie is kept
+               in ~/.cache/graf between runs and renewed when it expires.
+               PASS - reads it from stdin
+  --proxy-user USER
+               send USER in the X-WEBAUTH-USER header, for grafana behind an
+               auth proxy
+  --proxy-header NAME
+               the auth proxy's header, instead of X-WEBAUTH-USER
+  --cacert FILE
+               trust the CA certificates in FILE for grafana's certificate
+  --cert FILE, --key FILE
+               client certificate and its key, for grafanas that want mtls
+  --insecure   don't verify grafana's certificate, --insecure=false to verify
+               it when the config profile says not to
+  --url URL    grafana base url
+  --org ORG    use the organization with the id or name ORG instead of the
+               user's current one, '-' to pick one
+  --profile NAME
+               take defaults from the [NAME] profile of the config file
+               instead of [default]
+  -v, -vv, -vvv
+               print what graf is doing, the requests, the responses
+"#;
+
+const SEARCH_HELP: &str = r#"  SEARCH is any of
+  --query TEXT, --tag TAG, --folder FOLDER, --starred
+               only list dashboards with TEXT in their title, tagged with
+               every TAG, in the folder titled FOLDER (or with that uid) or
+               starred
+  --sort ORDER list dashboards by alpha-asc or alpha-desc (or views-desc and
+               such on grafana enterprise)
+"#;
+
+const RANGE_HELP: &str = r#"  RANGE is any of
+  --from FROM, --to TO
+               time specifiers for grafana (defaults to the dashboard's time
+               range, or now-5m, now)
+               either relative 'now-5m', RFC 3339 '2016-02-01T13:04:05+01:00',
+               'YYYY-MM-DD[ HH:MM:SS]' in UTC, or unix epoch seconds/millis
+  --interval SECS
+               interval in seconds between rows (defaults to the dashboard's
+               refresh when following, otherwise <terminal rows> / TO-FROM)
+"#;
+
+const DRAW_HELP: &str = r#"  DRAW is any of
+  --width COLS, --height ROWS
+               size to draw for, instead of the terminal's (or $COLUMNS and
+               $LINES, or 80x24 when not writing to a terminal)
+  --color WHEN auto (default), always or never. auto uses colors when writing
+               to a terminal and NO_COLOR isn't set. with 256 colors (TERM) or
+               truecolor (COLORTERM) series get grafana's colors
+  --graphics HOW
+               auto (default), sixel, kitty or none. auto asks the terminal
+               whether it shows sixel or kitty images, and if so the chart
+               is drawn in pixels as one of them instead of as text. rows
+               drawn when following are text
+  --tz TZ      timezone for time labels, an IANA name like 'Europe/Berlin',
+               'utc', or 'local' (defaults to the dashboard's timezone)
+  --compare DUR
+               also draw the same query shifted back by DUR ('1d', '1w'), in
+               faint colors
+"#;
+
+const OUTPUT_HELP: &str = r#"  --output FORMAT
+               print the rows as json (an object per line), csv or tsv instead
+               of drawing them, with a column for the time and each series
+               named like grafana's legend. when following, only new rows are
+               printed
+"#;
+
+const SVG_HELP: &str = r#"  --svg FILE   also draw the panel into FILE as an svg line chart, with
+               axes in the panel's unit, a legend and the series' colors, for
+               pasting into documents. when following, of the first range
+"#;
+
+const FOLLOW_HELP: &str = r#"  -f           follow, update data every SECS seconds, streaming
+               datasources are followed over grafana live instead
+"#;
+
+const TUI_HELP: &str = r#"  --tui        full screen interactive mode, switch panels with up/down, pan
+               with left/right, zoom with +/-, cycle variables with tab and v,
+               and toggle series with 1-9 (when following, refreshes every
+               SECS)
+"#;
+
+const PICK_HELP: &str = r#"  DASH is a dashboard's uid or title and PANEL a panel's title or its index
+  in 'graf ls panels'. left out, they're picked from a list by typing to
+  filter them, up/down and enter, as are variable values (tab marks several
+  values of multi-value variables). when stdin isn't a terminal, answer with
+  an index or part of the choice (comma separated for several values)
+"#;
+
+const NOTES: &str = r#"  the config file, $XDG_CONFIG_HOME/graf/config or ~/.config/graf/config, has
+  'key = value' lines under '[profile]' headers, keys before any header apply
+  to every profile. keys are url, user, token, token_file, credential_helper,
+  login, proxy_user, proxy_header, cacert, cert, key, insecure (true or
+  false), org, from, to, interval, tz, color, graphics, width and height,
+  flags on the command line override them
+
+  exit codes are 1 for usage and other errors, 3 when grafana couldn't be
+  reached (connection or certificate problems), 4 when the credentials were
+  turned down, 5 for missing permissions, 6 when a dashboard, panel,
+  folder, datasource or org wasn't found, 7 when grafana or a proxy in front of it
+  failed and 8 when the panel's query failed
+"#;
+
+/// graf's usage, or `command`'s
+fn usage(command: Option<Command>, short: bool) {
+    let Some(command) = command else {
+        println!("usage: graf [-h|--help] {GLOBAL_SYNOPSIS} COMMAND [ARGS]");
+        if short {
+            return;
+        }
+        println!("\n  talk to grafana from the terminal, list what's there, and draw or\n  query dashboard panels\n");
+        for command in Command::ALL {
+            let about = command.about().replace('\n', "\n    ");
+            println!("  graf {}\n    {about}\n", command.synopsis());
+        }
+        println!("  graf help COMMAND, or graf COMMAND --help, tells more about COMMAND\n");
+        print!("{GLOBAL_HELP}\n{NOTES}\n");
+        println!(
+            "{} {} by {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            env!("CARGO_PKG_AUTHORS")
+        );
+        return;
+    };
+    println!("usage: graf [OPTIONS] {}", command.synopsis());
+    if short {
+        return;
+    }
+    println!("\n  {}\n", command.about().replace('\n', "\n  "));
+    let flags = |help: &str, takes: bool| {
+        if takes {
+            println!("{help}");
+        }
+    };
+    flags(RANGE_HELP, command.takes("--from"));
+    flags(DRAW_HELP, command.takes("--width"));
+    flags(SEARCH_HELP, command.takes("--query"));
+    flags(OUTPUT_HELP, command.takes("--output"));
+    flags(SVG_HELP, command.takes("--svg"));
+    flags(FOLLOW_HELP, command.takes("-f"));
+    flags(TUI_HELP, command.takes("--tui"));
+    if command != Command::Ls {
+        println!("{PICK_HELP}");
+    }
+    println!("  OPTIONS go with every command, see graf --help");
+}
 
+fn main() {
     if std::process::Command::new("which")
         .arg("curl")
         .stdout(std::process::Stdio::null())
@@ -183,32 +406,182 @@ fn main() {
     let mut from = None;
     let mut to = None;
     let mut interval = None;
+    let mut tz = None;
+    let mut compare = None;
     let mut debug = 0;
     let mut follow = false;
-    let mut args = std::env::args();
+    let mut tui = false;
+    let mut width = None;
+    let mut height = None;
+    let mut color = None;
+    let mut graphics = None;
+    let mut search = Search::default();
+    let mut folder = None;
+    let mut org = None;
+    let mut profile = None;
+    let mut token_file = None;
+    let mut helper = None;
+    let mut login = None;
+    let mut proxy_user = None;
+    let mut proxy_header = None;
+    let mut tls = Tls::default();
+    let mut insecure = None;
+    let mut output = None;
+    let mut svg = None;
+    let mut help = None;
+    // the command and its arguments
+    let mut words = vec![];
+    // the flags given, for checking the command takes them
+    let mut given = vec![];
+    let mut args = std::env::args().skip(1);
     while let Some(arg) = args.next() {
+        if arg.starts_with('-') {
+            given.push(match arg.as_str() {
+                "-t" => "--token".to_string(),
+                "-u" => "--user".to_string(),
+                flag if flag.starts_with("--color=") => "--color".to_string(),
+                flag if flag.starts_with("--insecure=") => "--insecure".to_string(),
+                flag => flag.to_string(),
+            });
+        }
         match arg.as_str() {
-            f @ ("-h" | "--help") => {
-                usage(f == "-h");
-                return;
-            }
+            f @ ("-h" | "--help") => help = Some(f == "-h"),
             "-v" | "-vv" | "-vvv" => debug += arg.as_str().len() - 1,
             "-u" | "--user" => username = args.next(),
             "-t" | "--token" => token = args.next(),
+            "--token-file" => token_file = args.next(),
+            "--credential-helper" => helper = args.next(),
+            "--login" => login = args.next(),
+            "--proxy-user" => proxy_user = args.next(),
+            "--proxy-header" => proxy_header = args.next(),
+            "--cacert" => tls.cacert = args.next(),
+            "--cert" => tls.cert = args.next(),
+            "--key" => tls.key = args.next(),
+            "--insecure" => insecure = Some(true),
+            "--url" => url = args.next(),
             "--from" => from = args.next(),
             "--to" => to = args.next(),
             "--interval" => interval = args.next(),
+            "--tz" => tz = args.next(),
+            "--compare" => compare = args.next(),
             "-f" => follow = true,
-            flag if flag.starts_with("-") => {
+            "--tui" => tui = true,
+            "--width" => width = args.next(),
+            "--height" => height = args.next(),
+            "--color" => color = args.next(),
+            "--graphics" => graphics = args.next(),
+            "--output" => output = args.next(),
+            "--svg" => svg = args.next(),
+            "--query" => search.query = args.next(),
+            "--tag" => search.tags.extend(args.next()),
+            "--folder" => folder = args.next(),
+            "--starred" => search.starred = true,
+            "--sort" => search.sort = args.next(),
+            "--org" => org = args.next(),
+            "--profile" => profile = args.next(),
+            flag if flag.starts_with("--color=") => color = Some(flag[8..].to_string()),
+            flag if flag.starts_with("--insecure=") => match &flag[11..] {
+                "true" | "yes" | "1" => insecure = Some(true),
+                "false" | "no" | "0" => insecure = Some(false),
+                value => {
+                    eprintln!("error: --insecure is true or false, not {value:?}");
+                    std::process::exit(1);
+                }
+            },
+            flag if flag.starts_with('-') => {
                 eprintln!("error: unknown flag {flag:?}");
-                usage(true);
+                usage(None, true);
                 std::process::exit(1);
             }
-            _ => url = Some(arg),
+            _ => words.push(arg),
         }
     }
 
+    // graf URL was how graf used to be run, it's graf --url URL show
+    if words.first().is_some_and(|w| w.contains("://")) {
+        url = Some(words.remove(0));
+        words.insert(0, "show".to_string());
+    }
+    let command = match words.first().map(String::as_str) {
+        Some("help") => {
+            let command = words.get(1).and_then(|name| Command::named(name));
+            usage(command, help.unwrap_or(false));
+            return;
+        }
+        Some(name) => match Command::named(name) {
+            Some(command) => command,
+            None => {
+                eprintln!("error: unknown command {name:?}");
+                usage(None, true);
+                std::process::exit(1);
+            }
+        },
+        None if help.is_some() => {
+            usage(None, help == Some(true));
+            return;
+        }
+        None => {
+            eprintln!("error: COMMAND must be provided");
+            usage(None, true);
+            std::process::exit(1);
+        }
+    };
+    if let Some(short) = help {
+        usage(Some(command), short);
+        return;
+    }
+    for flag in &given {
+        let flag = flag.as_str();
+        let local = [
+            &SEARCH_FLAGS[..],
+            &RANGE_FLAGS,
+            &DRAW_FLAGS,
+            &["--output", "--svg", "-f", "--tui"],
+        ]
+        .concat();
+        if local.contains(&flag) && !command.takes(flag) {
+            eprintln!("error: {} doesn't take {flag}", command.name());
+            usage(Some(command), true);
+            std::process::exit(1);
+        }
+    }
+    let args = &words[1..];
+    if command != Command::Ls && args.len() > 2 {
+        eprintln!("error: {} takes a DASH and a PANEL", command.name());
+        usage(Some(command), true);
+        std::process::exit(1);
+    }
+
+    // anything not given on the command line comes from the config profile
+    let config = match config::load(profile.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
+    let setting = |key: &str| config.get(key).cloned();
+    url = url.or_else(|| setting("url"));
+    org = org.or_else(|| setting("org"));
+    from = from.or_else(|| setting("from"));
+    to = to.or_else(|| setting("to"));
+    interval = interval.or_else(|| setting("interval"));
+    tz = tz.or_else(|| setting("tz"));
+    color = color.or_else(|| setting("color"));
+    graphics = graphics.or_else(|| setting("graphics"));
+    width = width.or_else(|| setting("width"));
+    height = height.or_else(|| setting("height"));
+    proxy_header = proxy_header.or_else(|| setting("proxy_header"));
+    tls.cacert = tls.cacert.or_else(|| setting("cacert"));
+    tls.cert = tls.cert.or_else(|| setting("cert"));
+    tls.key = tls.key.or_else(|| setting("key"));
+    tls.insecure = insecure.unwrap_or_else(|| {
+        setting("insecure").is_some_and(|v| ["true", "yes", "1"].contains(&v.as_str()))
+    });
+
     let mut graf: Vec<&str> = vec![
+        // no progress meter, but still errors
+        "-sS",
         "-H",
         "Content-Type: application/json",
         "-H",
@@ -217,432 +590,296 @@ fn main() {
     if debug > 2 {
         graf.push("-v");
     }
-    let token = token.map(|t| format!("Authorization: Bearer {t}"));
-    if let Some(ref token) = token {
-        graf.extend(["-H", token]);
-    } else if let Some(ref userpass) = username {
-        graf.extend(["-u", userpass]);
-    } else {
-        eprintln!("error: either USER:PASS or TOKEN must be provided");
-        usage(true);
-        std::process::exit(1);
-    }
 
     let url = match url {
         Some(ref url) => url,
         None => {
-            eprintln!("error: URL must be provided");
-            usage(true);
+            eprintln!("error: URL must be provided, with --url or in the config file");
+            usage(Some(command), true);
             std::process::exit(1);
         }
     };
-    let now = i64::try_from(
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
-    )
-    .unwrap();
-    const TS_ERRSTR: &'static str = "valid values for FROM/TO are condensed ISO8601 UTC datetime '20160201T130405', grafana relative 'now-5m', or unix epoch '1678864718'";
-    let from = from.as_ref().map(String::as_str).unwrap_or("now-5m");
-    let mut from = match parse_instant(from, now) {
-        Some(time) => time,
-        None => {
-            eprintln!("error: {TS_ERRSTR}");
-            return;
+    let read_stdin = |auth: Auth| {
+        auth.read_stdin().unwrap_or_else(|err| {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        })
+    };
+    let auth = match (token, username, login, proxy_user) {
+        (Some(token), ..) => read_stdin(Auth::Token(token)),
+        (None, Some(userpass), ..) => read_stdin(Auth::Basic(userpass)),
+        (None, None, Some(userpass), _) => read_stdin(Auth::Session(userpass)),
+        (None, None, None, Some(user)) => Auth::Proxy(user),
+        (None, None, None, None) => {
+            match auth::find(token_file.as_deref(), helper.as_deref(), &config, url) {
+                Ok(Some(auth)) => auth,
+                Ok(None) => {
+                    eprintln!("error: either USER:PASS or TOKEN must be provided");
+                    usage(None, true);
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    std::process::exit(1);
+                }
+            }
         }
     };
-    let to = to.as_ref().map(String::as_str).unwrap_or("now");
-    if follow && to != "now" {
-        eprintln!("error: -f is only supported for --to now, disabling follow");
-        follow = false;
-    }
-    let mut to = match parse_instant(to, now) {
-        Some(time) => time,
-        None => {
-            eprintln!("error: {TS_ERRSTR}");
-            return;
+    let tz = match tz.as_deref().map(Tz::named) {
+        Some(Ok(tz)) => Some(tz),
+        Some(Err(err)) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    let compare = match compare.as_deref().map(|c| (c, parse_duration(c))) {
+        Some((_, Some(c))) if c > 0 => Some(c),
+        Some((c, _)) => {
+            eprintln!("error: invalid --compare duration {c:?}, expected e.g. '1d' or '1w'");
+            usage(Some(command), true);
+            std::process::exit(1);
         }
+        None => None,
     };
-    let interval = match interval.map(|s| i64::from_str_radix(&s, 10)) {
+    let interval = match interval.map(|s| s.parse::<i64>()) {
         Some(Ok(i)) => Some(i),
         Some(Err(_)) => {
             eprintln!("error: SECS must be a number");
-            usage(true);
-            return;
+            usage(Some(command), true);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    let size = |name, v: Option<String>| match v.map(|s| s.parse::<u16>()) {
+        Some(Ok(v)) if v > 0 => Some(v),
+        Some(_) => {
+            eprintln!("error: {name} must be a positive number");
+            usage(Some(command), true);
+            std::process::exit(1);
         }
         None => None,
     };
+    let (width, height) = (size("COLS", width), size("ROWS", height));
+    let output = match output.as_deref().map(|o| (o, Format::named(o))) {
+        Some((_, Some(_))) if compare.is_some() || tui => {
+            eprintln!("error: --output doesn't go with --compare or --tui");
+            std::process::exit(1);
+        }
+        Some((_, Some(format))) => Some(format),
+        Some((o, None)) => {
+            eprintln!("error: unknown output format {o:?}, expected json, csv or tsv");
+            usage(Some(command), true);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    if svg.is_some() && tui {
+        eprintln!("error: --svg doesn't go with --tui");
+        std::process::exit(1);
+    }
+    // SAFETY: just ffi
+    let tty = unsafe { libc::isatty(1) } == 1;
+    let env = |name: &str| std::env::var(name).unwrap_or_default();
+    let color = match color::Mode::detect(color.as_deref().unwrap_or("auto"), tty, env) {
+        Some(color) => color,
+        None => {
+            eprintln!("error: WHEN must be one of auto, always or never");
+            usage(Some(command), true);
+            std::process::exit(1);
+        }
+    };
+    // a picture is drawn once, rows that come later can only be text
+    let pictured =
+        matches!(command, Command::Show | Command::Render) && !follow && !tui && output.is_none();
+    let how = graphics.as_deref().unwrap_or("auto");
+    if !["auto", "sixel", "kitty", "none"].contains(&how) {
+        eprintln!("error: HOW must be one of auto, sixel, kitty or none");
+        usage(Some(command), true);
+        std::process::exit(1);
+    }
+    let graphics = match how {
+        _ if !pictured => None,
+        "auto" => term::caps().and_then(|caps| match (caps.kitty, caps.sixel) {
+            (true, _) => Some((Protocol::Kitty, caps)),
+            (false, true) => Some((Protocol::Sixel, caps)),
+            _ => None,
+        }),
+        // drawn even if the terminal didn't say it could
+        "sixel" => Some((Protocol::Sixel, term::caps().unwrap_or_default())),
+        "kitty" => Some((Protocol::Kitty, term::caps().unwrap_or_default())),
+        _ => None,
+    };
 
-    macro_rules! graf {
-        ($f:literal $(, $fargs:expr)* $(; $($arg:expr),* )?) => {{
-            let args:  &[&str] = &[$($($arg),*)?];
-            let urlarg = format!($f $(, $fargs)*);
-            if debug > 1 {
-                println!("-> get {graf:?} {urlarg:?} {args:?}");
-            }
-            let output = std::process::Command::new("curl")
-                .args(&graf)
-                .arg(&urlarg)
-                .args(args)
-                .output()
-                .expect(&url);
-
-            let json = serde_json::from_slice(&output.stdout);
-            match json {
-                Ok(json) => {
-                    if debug > 2 {
-                        println!("<- json: {json}");
-                        std::io::stderr().write(&output.stderr).unwrap();
-                    }
-                    Value(json)
-                },
+    let graf = graf.iter().map(|a| a.to_string()).chain(tls.curl_args());
+    let mut client = Client::new(url, graf.collect(), debug);
+    // stdout is for the chart or rows, -v goes to stderr
+    client.log(|line| eprintln!("{line}"));
+    // grafana live's websocket is opened without curl, so it gets the headers
+    let mut live_headers = vec![];
+    match auth {
+        Auth::Token(_) | Auth::Basic(_) => {
+            let authorization = format!("Authorization: {}", auth.header().unwrap());
+            client.secret("header", &authorization);
+            live_headers.push(authorization);
+        }
+        Auth::Session(userpass) => {
+            let session = match Session::new(url, &userpass) {
+                Ok(session) => session,
                 Err(err) => {
-                    eprintln!("error: unable to parse json response ({url}): {err}");
-                    if debug > 2 {
-                        let mut stderr = std::io::stderr().lock();
-                        stderr.write(b"<- text: \"").unwrap();
-                        stderr.write(&output.stdout).unwrap();
-                        stderr.write(b"\"\n").unwrap();
-                    }
-                    std::io::stderr().write(&output.stderr).unwrap();
+                    eprintln!("error: {err}");
                     std::process::exit(1);
                 }
-            }
-        }};
+            };
+            ok(client.session(session));
+        }
+        Auth::Proxy(user) => {
+            let name = proxy_header.as_deref().unwrap_or("X-WEBAUTH-USER");
+            client.header(format!("{name}: {user}"));
+            live_headers.push(format!("{name}: {user}"));
+        }
     }
 
-    fn prompt<'v>(select_a: &str, vals: &'v [Value], keys: &[&str]) -> &'v Value {
-        use std::ops::Index as _;
-        let mut buf = String::new();
-        if vals.len() == 1 {
-            return vals.index(0);
-        }
-        for (i, v) in vals.into_iter().enumerate() {
-            print!("{i} -");
-            match v.0 {
-                serde_json::Value::Null
-                | serde_json::Value::Bool(_)
-                | serde_json::Value::Number(_)
-                | serde_json::Value::String(_) => print!("{v}"),
-                serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
-                    for key in keys {
-                        print!(" {key}={}", v[key].0.to_string());
+    // requests go to the user's current org unless another is picked
+    if let Some(ref org) = org {
+        let id = match org.parse::<i64>() {
+            Ok(id) => id,
+            Err(_) => {
+                if org == "-" {
+                    let orgs = ok(client.orgs());
+                    let labels: Vec<_> = orgs
+                        .iter()
+                        .map(|o| {
+                            pick::label(&[("name", o.name.clone().into()), ("orgId", o.id.into())])
+                        })
+                        .collect();
+                    orgs[select_one("an org", &labels, debug)].id
+                } else {
+                    match ok(client.org_id(org)) {
+                        Some(id) => id,
+                        None => {
+                            eprintln!("error: no org named {org:?}");
+                            std::process::exit(6);
+                        }
                     }
                 }
             }
-            println!();
-        }
-        loop {
-            print!("Please select {select_a}: ");
-            std::io::stdout().flush().unwrap();
-            std::io::stdin().read_line(&mut buf).unwrap();
-            match buf.trim().parse::<usize>() {
-                Ok(i) => return vals.index(i),
-                Err(_) => {}
-            }
+        };
+        if debug > 1 {
+            eprintln!("org: {id}");
         }
+        client.header(format!("X-Grafana-Org-Id: {id}"));
+        live_headers.push(format!("X-Grafana-Org-Id: {id}"));
     }
-    // don't ask me why, but this wasn't working as a closure, but does work as
-    // a closure returned from a function
-    fn prompt1(debug: usize) -> impl for<'v> FnMut(&str, &'v [Value], &[&str]) -> &'v Value {
-        move |select_a, vals, keys| {
-            if debug > 1 {
-                println!(
-                    "prompt {select_a} from {}",
-                    serde_json::to_string_pretty(unsafe {
-                        std::mem::transmute::<_, &[serde_json::Value]>(vals)
-                    })
-                    .unwrap()
-                );
-            }
-            prompt(select_a, vals, keys)
+
+    let dashboards = Dashboards { search, folder };
+    if command == Command::Ls {
+        if let Err(err) = ls::run(&client, &dashboards, args, debug) {
+            exit(err);
         }
+        return;
     }
-    let mut prompt = prompt1(debug);
-
-    let (rows, cols) = {
-        let mut winsz = libc::winsize {
-            ws_col: 10,
-            ws_row: 10,
-            ws_xpixel: 0,
-            ws_ypixel: 0,
-        };
-        // SAFETY: just ffi
-        let ret = unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut winsz as *mut _) };
-        assert_eq!(ret, 0, "ioctl");
-        (winsz.ws_row, winsz.ws_col)
+    let dash = dashboards.get(&client, args.first().map(String::as_str), debug);
+    let opts = panel::Options {
+        command,
+        url: url.clone(),
+        tls,
+        live_headers,
+        from,
+        to,
+        interval,
+        tz,
+        compare,
+        follow: follow || command == Command::Watch,
+        tui,
+        width,
+        height,
+        color,
+        output,
+        svg,
+        graphics,
+        debug,
     };
-    if debug > 1 {
-        println!("rows:{rows} cols:{cols}");
-    }
-    let interval = interval.unwrap_or_else(|| (to - from) / i64::from(rows));
-
-    let pageres = graf!("{url}/api/search?type=dash-db");
-    let dash = prompt("a dashboard", pageres.a(), &["title", "uid"]);
-    let dashuid = dash["uid"].s();
-    let dash = graf!("{url}/api/dashboards/uid/{dashuid}");
-    let panels = &dash["dashboard"]["panels"];
-    let panel = prompt("a panel", panels.a(), &["title"]);
-    let mut templating = std::collections::HashMap::new();
-    for tmpl in dash["dashboard"]["templating"]["list"].a() {
-        let name = tmpl["name"].s();
-        println!("templating: name={name} query={}", tmpl["query"].s());
-        let q = serde_json::json!({
-            "queries": [{
-                "datasource": tmpl["datasource"].0,
-                "query": tmpl["query"].0,
-                "rawQuery": true,
-            }],
-
-            // XXX: this doesn't seem to actually limit it to values that existed in that window?
-            // for instance, I've restarted telegraf container, had two hostnames, and set the
-            // window so small that influx data only exists for the one host, but this query
-            // will return both host values. If I choose the older one, I just get back "no data".
-            "from": (from / 1000).to_string(),
-            "to": (to / 1000).to_string(),
-        });
-        let res = graf!("{url}/api/ds/query"; "-d", &q.to_string());
-        let vs: std::collections::HashSet<_> = res["results"]["A"]["frames"]
-            .a()
-            .into_iter()
-            .flat_map(|f| f["data"]["values"].a().into_iter())
-            .flat_map(|v| v.a().into_iter())
-            .map(|v| match &v.0 {
-                serde_json::Value::String(s) => s.to_string(),
-                v => v.to_string(),
-            })
-            .collect();
-        let vs: Vec<_> = vs.into_iter().map(|v| Value(v.into())).collect();
-        let v: Value = prompt(&format!("a value for ${name}"), &vs, &[]).clone();
-        templating.insert(
-            name.to_string(),
-            match v.0 {
-                serde_json::Value::String(s) => s,
-                _ => unreachable!("values were created from strings"),
-            },
-        );
+    if let Err(err) = panel::run(&client, &dash, args.get(1).map(String::as_str), opts) {
+        exit(err);
     }
+}
 
-    let target = if panel.0.get("targets").is_some() {
-        prompt("a target", panel["targets"].a(), &["refId"]).clone()
-    } else if let Some(datasource) = panel.0.get("datasource") {
-        Value(serde_json::json!({ "datasource": datasource, "refId": "A" }))
-    } else {
-        eprintln!("error: cannot extract target from panel: {}", panel.0);
-        std::process::exit(1);
-    };
-
-    let refid = target["refId"].s();
-    let mut query = target.0.clone();
-    fn visit_replace(v: &mut serde_json::Value, tmpls: &std::collections::HashMap<String, String>) {
-        match v {
-            serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
-            }
-            serde_json::Value::String(s) => {
-                for (p, to) in tmpls {
-                    *s = s.replace(&format!("${p}"), to);
-                }
-            }
-            serde_json::Value::Array(elems) => {
-                for e in elems {
-                    visit_replace(e, tmpls);
-                }
-            }
-            serde_json::Value::Object(map) => {
-                for v in map.values_mut() {
-                    visit_replace(v, tmpls);
-                }
-            }
-        }
+/// asks for one or, with `multi`, any number of `labels`, and gives up
+/// quietly if the user does
+fn select(what: &str, labels: &[String], multi: bool, debug: usize) -> Vec<usize> {
+    if debug > 1 {
+        eprintln!("prompt {what} from {labels:#?}");
     }
-    visit_replace(&mut query, &templating);
-    {
-        let query = query.as_object_mut().unwrap();
-        query.insert("maxDataPoints".to_string(), rows.into());
-        query.insert("intervalMs".to_string(), (interval * 1000).into());
-        if let (Some(datasource), None) = (panel.0.get("datasource"), query.get("datasource")) {
-            let mut datasource = datasource.clone();
-            if datasource.is_string() {
-                let ds = graf!(
-                    "{url}/api/datasources/name/{}",
-                    datasource.as_str().unwrap()
-                );
-                datasource = serde_json::json!({"uid": ds.0["uid"]});
-            }
-            query.insert("datasource".to_string(), datasource);
+    match pick::pick(what, labels, multi) {
+        Some(picked) => picked,
+        None if labels.is_empty() => {
+            eprintln!("error: nothing to select {what} from");
+            std::process::exit(1);
         }
+        None => std::process::exit(1),
     }
-    let get_values = |from: i64, to: i64| {
-        let qarg = serde_json::Value::Object(serde_json::Map::from_iter([
-            (
-                "queries".to_string(),
-                serde_json::Value::Array(vec![query.clone()]),
-            ),
-            ("from".to_string(), (from * 1000).to_string().into()),
-            ("to".to_string(), (to * 1000).to_string().into()),
-        ]))
-        .to_string();
-        if debug > 0 {
-            println!("query: {}", qarg);
-        }
-        graf!("{url}/api/ds/query"; "-d", &qarg)
-    };
-    let parse_values = |vals: &Value| {
-        let times: Vec<_> = vals[0]["data"]["values"][0]
-            .a()
-            .into_iter()
-            .map(|v| v.i())
-            .collect();
-        // frames -> data -> values -> _
-        let vals: Vec<Vec<Vec<_>>> = vals
-            .a()
-            .into_iter()
-            .map(|v| {
-                v["data"]["values"]
-                    .a()
-                    .into_iter()
-                    .skip(1)
-                    .map(|v| v.a().into_iter().map(|v| v.0.as_f64()).collect())
-                    .collect()
-            })
-            .collect();
-        (times, vals)
-    };
+}
 
-    let dsquery = get_values(from, to);
-    let vals = &dsquery["results"][refid]["frames"];
-    if vals.a().is_empty() {
-        println!("no data");
-        return;
-    }
-    let (mut times, vals) = parse_values(vals);
-
-    let min = vals
-        .iter()
-        .flat_map(|vs| vs.iter())
-        .flat_map(|vs| vs.iter())
-        .filter_map(|v| v.as_ref())
-        .fold(f64::INFINITY, |acc, &x| if x < acc { x } else { acc });
-    let max = vals
-        .iter()
-        .flat_map(|vs| vs.iter())
-        .flat_map(|vs| vs.iter())
-        .filter_map(|v| v.as_ref())
-        .fold(-f64::INFINITY, |acc, &x| if x > acc { x } else { acc });
-    // make room for time stamps "13:04:05 "
-    let cols = cols - 9;
-    if debug > 1 {
-        let log_base = (max - min).log10();
-        println!("log_base:{log_base} min:{min} max:{max} cols:{cols}");
-    }
+fn select_one(what: &str, labels: &[String], debug: usize) -> usize {
+    select(what, labels, false, debug)[0]
+}
 
-    let scale = f64::from(cols - 1) / (max - min);
-    let scale = |vals: Vec<Vec<Vec<Option<f64>>>>| -> Vec<Vec<Vec<Option<u16>>>> {
-        let scale = |v: f64| {
-            let v = (v - min) * scale;
-            if v.is_finite() && v >= 0.0 && v < f64::from(cols) {
-                // SAFETY: finite (and not NaN), and fits in u16 asserted above
-                Some(unsafe { v.to_int_unchecked::<u16>() })
-            } else {
-                None
-            }
+/// the dashboards the search flags leave to pick from
+struct Dashboards {
+    search: Search,
+    folder: Option<String>,
+}
+
+impl Dashboards {
+    fn list(&self, client: &Client) -> Vec<SearchHit> {
+        let mut search = Search {
+            folder_uids: vec![],
+            ..self.search.clone()
         };
-        vals.into_iter()
-            .map(|vvs| {
-                vvs.into_iter()
-                    .map(|vs| vs.into_iter().map(|v| v.and_then(scale)).collect())
-                    .collect()
-            })
-            .collect()
-    };
-    let mut scaled_vals: Vec<Vec<_>> = scale(vals);
-
-    let colors = [31, 32, 33, 34, 35, 36];
-    let mut i0 = 0;
-    loop {
-        for i in 1..times.len() {
-            let mut hdr = vec![];
-            if (i0 + i) % usize::from(rows) == 1 {
-                use std::io::Write as _;
-                let step = (max - min) / f64::from(cols) * 16.0;
-                for j in 0..=((cols + 1) / 16) {
-                    write!(hdr, " {:<15.2}", min + step * f64::from(j)).expect("write header");
+        if let Some(ref folder) = self.folder {
+            match ok(client.folder_uid(folder)) {
+                Some(uid) => search.folder_uids.push(uid),
+                None => {
+                    eprintln!("error: no folder with the title or uid {folder:?}");
+                    std::process::exit(6);
                 }
             }
-            if (i0 + i) % 5 == 1 {
-                let mut buf = [b' '; 9];
-                let time_s = timestamp(times[i], &mut buf);
-                print!("{time_s} ");
-            } else {
-                print!("         ");
-            }
-            let mut hdr = hdr.into_iter();
-            for j in 0..cols {
-                let s = scaled_vals.iter().flat_map(|v| v.iter()).enumerate();
-                let acc = hdr
-                    .next()
-                    .filter(|b| *b != b' ')
-                    .map(|b| (90, char::from(b)));
-                let fold = |k, vs: &[_]| {
-                    let (x, xp) = match (vs[i], vs[i - 1]) {
-                        (Some(x), Some(xp)) => (x, xp),
-                        _ => return None,
-                    };
-                    if (x < j && j < xp) || (xp < j && j < x) {
-                        Some((colors[k % colors.len()], '-'))
-                    } else if x == j && xp == j {
-                        Some((colors[k % colors.len()], '|'))
-                    } else if x == j {
-                        Some((colors[k % colors.len()], '.'))
-                    } else if xp == j {
-                        Some((colors[k % colors.len()], '\''))
-                    } else {
-                        None
-                    }
-                };
-                let s = s
-                    .fold(acc, |acc, (k, vs)| acc.or_else(|| fold(k, vs)))
-                    .unwrap_or_else(|| if j % 16 == 0 { (90, '|') } else { (0, ' ') });
-                if s.0 == 0 {
-                    print!("{}", s.1);
-                } else {
-                    print!("\x1b[{}m{}\x1b[0m", s.0, s.1);
-                }
-            }
-            println!();
-        }
-
-        if !follow {
-            return;
         }
+        ok(client.search(&search))
+    }
 
-        i0 += times.len() - 1;
-        from = to - interval;
-        loop {
-            to += interval;
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            let now = i64::try_from(now).unwrap();
-            while now > to + interval {
-                to += interval;
-            }
-            if now < to {
-                let lag = u64::try_from(to - now).unwrap();
-                std::thread::sleep(std::time::Duration::from_secs(lag));
+    /// the dashboard with the uid or title `dash`, or the one picked
+    fn get(&self, client: &Client, dash: Option<&str>, debug: usize) -> Dashboard {
+        let dashes = self.list(client);
+        let uid = match dash {
+            Some(dash) => match dashes.iter().find(|d| d.uid == dash || d.title == dash) {
+                Some(d) => &d.uid,
+                // it may be a uid the search didn't get to
+                None => dash,
+            },
+            None => {
+                let labels: Vec<_> = dashes
+                    .iter()
+                    .map(|d| {
+                        pick::label(&[
+                            ("folderTitle", d.folder_title.clone().into()),
+                            ("title", d.title.clone().into()),
+                            ("uid", d.uid.clone().into()),
+                            ("tags", d.tags.clone().into()),
+                        ])
+                    })
+                    .collect();
+                &dashes[select_one("a dashboard", &labels, debug)].uid
             }
-            let dsquery = get_values(from, to);
-            let vals = &dsquery["results"][refid]["frames"];
-            if vals.a().is_empty() {
-                println!("no data");
-                continue;
+        };
+        match client.dashboard(uid) {
+            Ok(dash) => dash,
+            Err(Failure { status: 404, .. }) => {
+                eprintln!("error: no dashboard with the uid or title {uid:?}");
+                std::process::exit(6);
             }
-            let (times0, vals) = parse_values(vals);
-            times = times0;
-            scaled_vals = scale(vals);
-            break;
+            Err(failure) => fail(&failure),
         }
     }
 }
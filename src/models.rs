@@ -0,0 +1,739 @@
+// typed views of the json grafana sends, decoded with serde. missing and null
+// fields get defaults, as grafana leaves out a lot of what it doesn't need,
+// but a field of the wrong type is an error naming where it was, like
+// "dashboard.panels[2].targets[0].refId: invalid type: integer `1`, expected
+// a string". queries and field configs are passed on to grafana or looked
+// into loosely, so they're kept as json.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::{DeserializeOwned, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+
+/// `json` as a `T`, or what's wrong with it and where
+pub fn decode<T: DeserializeOwned>(json: &serde_json::Value) -> Result<T, String> {
+    serde_path_to_error::deserialize(json).map_err(|err| {
+        let path = match err.path().to_string() {
+            path if path == "." => "response".to_string(),
+            path => path,
+        };
+        format!("{path}: {}", err.into_inner())
+    })
+}
+
+// null is the same as leaving the field out
+fn or_default<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::deserialize(d)?.unwrap_or_default())
+}
+
+// and so is an empty string
+fn non_empty<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(d)?.filter(|s| !s.is_empty()))
+}
+
+// a whole number, which grafana sometimes writes as a float like 100.0
+fn number<'de, D: Deserializer<'de>>(d: D) -> Result<Option<i64>, D::Error> {
+    let n = Option::<serde_json::Number>::deserialize(d)?;
+    Ok(n.and_then(|n| n.as_i64().or_else(|| Some(n.as_f64()? as i64))))
+}
+
+fn id<'de, D: Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+    Ok(number(d)?.unwrap_or_default())
+}
+
+fn general() -> String {
+    "General".to_string()
+}
+
+// dashboards outside of any folder are in the general one
+fn folder_title<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(Option::deserialize(d)?.unwrap_or_else(general))
+}
+
+fn info() -> String {
+    "info".to_string()
+}
+
+fn severity<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(Option::deserialize(d)?.unwrap_or_else(info))
+}
+
+// a duration like "1m", or an empty string or false for no refresh
+fn refresh<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    struct Refresh;
+    impl<'de> Visitor<'de> for Refresh {
+        type Value = Option<String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a duration or false")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+
+        fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+    }
+    d.deserialize_any(Refresh)
+}
+
+// one value, or a list of them for multi-value variables
+fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    struct OneOrMany;
+    impl<'de> Visitor<'de> for OneOrMany {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string or a list of them")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(vec![v.to_string()])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut values = vec![];
+            while let Some(value) = seq.next_element()? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(vec![])
+        }
+    }
+    d.deserialize_any(OneOrMany)
+}
+
+/// an entry of /api/search
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    #[serde(default, deserialize_with = "or_default")]
+    pub uid: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub title: String,
+    #[serde(default = "general", deserialize_with = "folder_title")]
+    pub folder_title: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub tags: Vec<String>,
+}
+
+/// an entry of /api/folders
+#[derive(Deserialize)]
+pub struct Folder {
+    #[serde(default, deserialize_with = "or_default")]
+    pub uid: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub title: String,
+}
+
+/// an entry of /api/datasources
+#[derive(Deserialize)]
+pub struct Datasource {
+    #[serde(default, deserialize_with = "or_default")]
+    pub uid: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+    #[serde(rename = "type", default, deserialize_with = "or_default")]
+    pub kind: String,
+}
+
+/// an entry of /api/user/orgs
+#[derive(Deserialize)]
+pub struct Org {
+    #[serde(rename = "orgId", default, deserialize_with = "id")]
+    pub id: i64,
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+}
+
+/// /api/dashboards/uid/UID
+#[derive(Deserialize)]
+#[serde(from = "DashboardResponse")]
+pub struct Dashboard {
+    pub timezone: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub refresh: Option<String>,
+    pub panels: Vec<Panel>,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Deserialize)]
+struct DashboardResponse {
+    #[serde(default, deserialize_with = "or_default")]
+    dashboard: DashboardJson,
+}
+
+#[derive(Default, Deserialize)]
+struct DashboardJson {
+    timezone: Option<String>,
+    #[serde(default, deserialize_with = "or_default")]
+    time: Range,
+    #[serde(default, deserialize_with = "refresh")]
+    refresh: Option<String>,
+    #[serde(default, deserialize_with = "or_default")]
+    panels: Vec<Panel>,
+    #[serde(default, deserialize_with = "or_default")]
+    templating: Templating,
+}
+
+#[derive(Default, Deserialize)]
+struct Range {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct Templating {
+    #[serde(default, deserialize_with = "or_default")]
+    list: Vec<Variable>,
+}
+
+impl From<DashboardResponse> for Dashboard {
+    fn from(res: DashboardResponse) -> Dashboard {
+        let dash = res.dashboard;
+        Dashboard {
+            timezone: dash.timezone,
+            from: dash.time.from,
+            to: dash.time.to,
+            refresh: dash.refresh,
+            panels: dash.panels,
+            variables: dash.templating.list,
+        }
+    }
+}
+
+impl Dashboard {
+    /// the panels with queries to run, the ones in collapsed rows listed
+    /// under the row
+    pub fn queried_panels(&self) -> Vec<&Panel> {
+        self.panels
+            .iter()
+            .flat_map(|p| match p.kind.as_str() {
+                "row" => p.panels.iter().collect(),
+                _ => vec![p],
+            })
+            .filter(|p| p.kind != "row" && p.targets().is_some_and(|ts| !ts.is_empty()))
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Panel {
+    #[serde(default, deserialize_with = "or_default")]
+    pub title: String,
+    #[serde(rename = "type", default, deserialize_with = "or_default")]
+    pub kind: String,
+    // a datasource name, or an object with its uid
+    pub datasource: Option<serde_json::Value>,
+    // None for panels that don't list any, like text panels
+    pub targets: Option<Vec<Target>>,
+    #[serde(default, deserialize_with = "non_empty")]
+    pub time_from: Option<String>,
+    #[serde(default, deserialize_with = "non_empty")]
+    pub time_shift: Option<String>,
+    #[serde(default, deserialize_with = "non_empty")]
+    pub interval: Option<String>,
+    #[serde(default, deserialize_with = "number")]
+    pub max_data_points: Option<i64>,
+    #[serde(default, deserialize_with = "or_default")]
+    pub hide_time_override: bool,
+    #[serde(default)]
+    pub field_config: serde_json::Value,
+    // the panels of a collapsed row
+    #[serde(default, deserialize_with = "or_default")]
+    pub panels: Vec<Panel>,
+}
+
+impl Panel {
+    /// the panel's targets, or a stand in target for panels that only name a
+    /// datasource
+    pub fn targets(&self) -> Option<Vec<Target>> {
+        match (&self.targets, &self.datasource) {
+            (Some(targets), _) => Some(targets.clone()),
+            (None, Some(datasource)) => Some(vec![Target {
+                ref_id: "A".to_string(),
+                query: serde_json::json!({ "datasource": datasource, "refId": "A" }),
+            }]),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(from = "TargetJson")]
+pub struct Target {
+    pub ref_id: String,
+    // the target as it is, the query grafana gets
+    pub query: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(expecting = "an object")]
+struct TargetJson {
+    #[serde(rename = "refId")]
+    ref_id: Option<String>,
+    #[serde(flatten)]
+    rest: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<TargetJson> for Target {
+    fn from(target: TargetJson) -> Target {
+        let mut query = target.rest;
+        if let Some(ref_id) = &target.ref_id {
+            query.insert("refId".to_string(), ref_id.clone().into());
+        }
+        Target {
+            ref_id: target.ref_id.unwrap_or_else(|| "A".to_string()),
+            query: query.into(),
+        }
+    }
+}
+
+/// a templating variable
+#[derive(Deserialize)]
+pub struct Variable {
+    #[serde(default, deserialize_with = "or_default")]
+    pub name: String,
+    #[serde(default)]
+    pub datasource: serde_json::Value,
+    // a string, or an object for newer datasources
+    #[serde(default)]
+    pub query: serde_json::Value,
+    #[serde(default, deserialize_with = "or_default")]
+    pub multi: bool,
+    #[serde(default, deserialize_with = "current")]
+    pub current: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct Current {
+    #[serde(default, deserialize_with = "one_or_many")]
+    value: Vec<String>,
+}
+
+fn current<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    Ok(or_default::<D, Current>(d)?.value)
+}
+
+/// /api/ds/query, a result for each refId
+#[derive(Deserialize)]
+pub struct QueryResponse {
+    #[serde(default, deserialize_with = "or_default")]
+    pub results: HashMap<String, QueryResult>,
+}
+
+/// `results[refId]` of /api/ds/query
+#[derive(Default, Deserialize)]
+pub struct QueryResult {
+    #[serde(default, deserialize_with = "or_default")]
+    pub frames: Vec<Frame>,
+    #[serde(default, deserialize_with = "non_empty")]
+    pub error: Option<String>,
+    #[serde(default, deserialize_with = "number")]
+    pub status: Option<i64>,
+}
+
+/// a data frame, its fields and a column of values for each of them
+#[derive(Deserialize)]
+#[serde(from = "FrameJson")]
+pub struct Frame {
+    pub fields: Vec<Field>,
+    pub values: Vec<Vec<serde_json::Value>>,
+    // the grafana live channel of a streaming datasource
+    pub channel: Option<String>,
+    pub notices: Vec<Notice>,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    #[serde(default, deserialize_with = "or_default")]
+    schema: Schema,
+    #[serde(default, deserialize_with = "or_default")]
+    data: Data,
+}
+
+#[derive(Default, Deserialize)]
+struct Schema {
+    #[serde(default, deserialize_with = "or_default")]
+    fields: Vec<Field>,
+    #[serde(default, deserialize_with = "or_default")]
+    meta: Meta,
+}
+
+#[derive(Default, Deserialize)]
+struct Meta {
+    channel: Option<String>,
+    #[serde(default, deserialize_with = "or_default")]
+    notices: Vec<Notice>,
+}
+
+#[derive(Default, Deserialize)]
+struct Data {
+    #[serde(default, deserialize_with = "or_default")]
+    values: Vec<Option<Vec<serde_json::Value>>>,
+}
+
+impl From<FrameJson> for Frame {
+    fn from(frame: FrameJson) -> Frame {
+        Frame {
+            fields: frame.schema.fields,
+            values: (frame.data.values.into_iter())
+                .map(Option::unwrap_or_default)
+                .collect(),
+            channel: frame.schema.meta.channel,
+            notices: frame.schema.meta.notices,
+        }
+    }
+}
+
+impl Frame {
+    /// the time of each row, from the first column
+    pub fn times(&self) -> Vec<i64> {
+        let times = self.values.first().map_or(&[][..], Vec::as_slice);
+        times
+            .iter()
+            .map(|t| {
+                t.as_i64()
+                    .or_else(|| Some(t.as_f64()? as i64))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// the values of each series after the time column
+    pub fn series(&self) -> Vec<Vec<Option<f64>>> {
+        self.values
+            .iter()
+            .skip(1)
+            .map(|vs| vs.iter().map(serde_json::Value::as_f64).collect())
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(from = "FieldJson")]
+pub struct Field {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub display_name: Option<String>,
+    // the color field config the datasource set, if any
+    pub color: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct FieldJson {
+    #[serde(default, deserialize_with = "or_default")]
+    name: String,
+    #[serde(default, deserialize_with = "or_default")]
+    labels: BTreeMap<String, String>,
+    #[serde(default, deserialize_with = "or_default")]
+    config: FieldConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FieldConfig {
+    #[serde(rename = "displayNameFromDS")]
+    display_name_from_ds: Option<String>,
+    display_name: Option<String>,
+    #[serde(default)]
+    color: serde_json::Value,
+}
+
+impl From<FieldJson> for Field {
+    fn from(field: FieldJson) -> Field {
+        let config = field.config;
+        Field {
+            name: field.name,
+            labels: field.labels,
+            display_name: config.display_name_from_ds.or(config.display_name),
+            color: config.color,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Notice {
+    #[serde(default = "info", deserialize_with = "severity")]
+    pub severity: String,
+    #[serde(default, deserialize_with = "or_default")]
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashboard() {
+        let dash: Dashboard = decode(&serde_json::json!({"dashboard": {
+            "timezone": "utc",
+            "time": {"from": "now-6h", "to": "now"},
+            "refresh": "1m",
+            "panels": [
+                {"title": "cpu", "type": "timeseries", "targets": [{"refId": "B", "expr": "up"}],
+                 "timeFrom": "1h", "timeShift": "", "maxDataPoints": 100.0},
+                {"title": "notes", "type": "text"},
+                {"type": "row", "panels": [
+                    {"title": "mem", "datasource": {"uid": "prom"}}
+                ]}
+            ],
+            "templating": {"list": [
+                {"name": "host", "multi": true, "current": {"value": ["a", "b"]}},
+                {"name": "env", "current": {"value": "prod"}}
+            ]}
+        }}))
+        .unwrap();
+        assert_eq!(dash.timezone.as_deref(), Some("utc"));
+        assert_eq!(dash.from.as_deref(), Some("now-6h"));
+        assert_eq!(dash.refresh.as_deref(), Some("1m"));
+        let cpu = &dash.panels[0];
+        assert_eq!(cpu.time_from.as_deref(), Some("1h"));
+        // empty strings are the same as leaving it out
+        assert_eq!(cpu.time_shift, None);
+        assert_eq!(cpu.max_data_points, Some(100));
+        assert_eq!(cpu.targets.as_ref().unwrap()[0].ref_id, "B");
+        assert_eq!(cpu.targets.as_ref().unwrap()[0].query["expr"], "up");
+        assert!(dash.panels[1].targets.is_none());
+
+        // the text panel has nothing to query, the row's panel has a stand in target
+        let queried: Vec<_> = dash.queried_panels().iter().map(|p| &p.title).collect();
+        assert_eq!(queried, ["cpu", "mem"]);
+        let mem = dash.queried_panels()[1].targets().unwrap();
+        assert_eq!(mem[0].query["datasource"]["uid"], "prom");
+
+        assert_eq!(dash.variables[0].current, ["a", "b"]);
+        assert!(dash.variables[0].multi);
+        assert_eq!(dash.variables[1].current, ["prod"]);
+        assert!(!dash.variables[1].multi);
+    }
+
+    #[test]
+    fn defaults() {
+        // grafana leaves out whatever it doesn't need
+        let dash: Dashboard =
+            decode(&serde_json::json!({"dashboard": {"refresh": false}})).unwrap();
+        assert!(dash.timezone.is_none() && dash.from.is_none() && dash.refresh.is_none());
+        assert!(dash.panels.is_empty() && dash.variables.is_empty());
+        let dash: Dashboard = decode(&serde_json::json!({})).unwrap();
+        assert!(dash.panels.is_empty());
+
+        let hit: SearchHit = decode(&serde_json::json!({"uid": "x", "tags": null})).unwrap();
+        assert_eq!(hit.folder_title, "General");
+        assert!(hit.title.is_empty() && hit.tags.is_empty());
+        let org: Org = decode(&serde_json::json!({"name": "main"})).unwrap();
+        assert_eq!(org.id, 0);
+        let target: Target = decode(&serde_json::json!({})).unwrap();
+        assert_eq!(target.ref_id, "A");
+        let notice: Notice = decode(&serde_json::json!({"text": "slow"})).unwrap();
+        assert_eq!(notice.severity, "info");
+        let res: QueryResult = decode(&serde_json::json!({"error": ""})).unwrap();
+        assert!(res.error.is_none() && res.frames.is_empty() && res.status.is_none());
+    }
+
+    #[test]
+    fn frames() {
+        let res: QueryResult = decode(&serde_json::json!({
+            "status": 200,
+            "frames": [{
+                "schema": {
+                    "fields": [
+                        {"name": "Time"},
+                        {"name": "value", "labels": {"job": "api"},
+                         "config": {"displayNameFromDS": "api", "color": {"mode": "fixed"}}}
+                    ],
+                    "meta": {"channel": "ds/x/y", "notices": [{"severity": "warning", "text": "t"}]}
+                },
+                "data": {"values": [[1000, 2000.0], [1.5, null]]}
+            }]
+        }))
+        .unwrap();
+        assert_eq!(res.status, Some(200));
+        let frame = &res.frames[0];
+        assert_eq!(frame.times(), [1000, 2000]);
+        assert_eq!(frame.series(), [vec![Some(1.5), None]]);
+        assert_eq!(frame.channel.as_deref(), Some("ds/x/y"));
+        assert_eq!(frame.notices[0].severity, "warning");
+        let field = &frame.fields[1];
+        assert_eq!(field.labels["job"], "api");
+        assert_eq!(field.display_name.as_deref(), Some("api"));
+        assert_eq!(field.color["mode"], "fixed");
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let err = decode::<Dashboard>(&serde_json::json!({"dashboard": {
+            "panels": [{}, {}, {"targets": [{"refId": 1}]}]
+        }}))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err,
+            "dashboard.panels[2].targets[0].refId: invalid type: integer `1`, expected a string"
+        );
+        for (json, err) in [
+            (
+                serde_json::json!({"dashboard": {"panels": {}}}),
+                "dashboard.panels: invalid type: map, expected a sequence",
+            ),
+            (
+                serde_json::json!({"dashboard": {"time": {"from": 5}}}),
+                "dashboard.time.from: invalid type: integer `5`, expected a string",
+            ),
+            (
+                serde_json::json!({"dashboard": {"panels": [{"targets": ["up"]}]}}),
+                "dashboard.panels[0].targets[0]: invalid type: string \"up\", expected an object",
+            ),
+            (
+                serde_json::json!({"dashboard": {"templating": {"list": [{"current": {"value": 3}}]}}}),
+                "dashboard.templating.list[0].current.value: invalid type: integer `3`, expected a string or a list of them",
+            ),
+        ] {
+            assert_eq!(decode::<Dashboard>(&json).err().as_deref(), Some(err));
+        }
+        let err = decode::<Vec<Folder>>(&serde_json::json!("folders"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            "response: invalid type: string \"folders\", expected a sequence"
+        );
+        let err = decode::<Field>(&serde_json::json!({"labels": {"job": 1}}))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            "labels.job: invalid type: integer `1`, expected a string"
+        );
+        let err = decode::<Field>(&serde_json::json!({"labels": []}))
+            .err()
+            .unwrap();
+        assert_eq!(err, "labels: invalid type: sequence, expected a map");
+        let err =
+            decode::<QueryResult>(&serde_json::json!({"frames": [{"data": {"values": [1]}}]}))
+                .err()
+                .unwrap();
+        assert_eq!(
+            err,
+            "frames[0].data.values[0]: invalid type: integer `1`, expected a sequence"
+        );
+        let err = decode::<QueryResponse>(&serde_json::json!({"results": {"A": {
+            "frames": [{"schema": {"fields": [{"name": 2}]}}]
+        }}}))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err,
+            "results.A.frames[0].schema.fields[0].name: invalid type: integer `2`, expected a string"
+        );
+    }
+
+    #[test]
+    fn no_panics() {
+        // every model over json of every shape, in every field it reads
+        let shapes = [
+            serde_json::json!(null),
+            serde_json::json!(true),
+            serde_json::json!(-1.5e300),
+            serde_json::json!(u64::MAX),
+            serde_json::json!("x"),
+            serde_json::json!([]),
+            serde_json::json!([null, 1, "x", [], {}]),
+            serde_json::json!({}),
+        ];
+        let keys = [
+            "dashboard",
+            "time",
+            "from",
+            "to",
+            "timezone",
+            "refresh",
+            "panels",
+            "templating",
+            "list",
+            "title",
+            "type",
+            "datasource",
+            "targets",
+            "timeFrom",
+            "timeShift",
+            "interval",
+            "maxDataPoints",
+            "hideTimeOverride",
+            "fieldConfig",
+            "refId",
+            "name",
+            "query",
+            "multi",
+            "current",
+            "value",
+            "results",
+            "A",
+            "frames",
+            "error",
+            "status",
+            "schema",
+            "meta",
+            "fields",
+            "data",
+            "values",
+            "channel",
+            "notices",
+            "labels",
+            "config",
+            "displayName",
+            "displayNameFromDS",
+            "color",
+            "severity",
+            "text",
+            "uid",
+            "folderTitle",
+            "tags",
+            "orgId",
+        ];
+        // nests `shape` under each key, and as each key's list element
+        let mut docs = shapes.to_vec();
+        for shape in &shapes {
+            for key in keys {
+                let one = serde_json::json!({ key: shape });
+                docs.push(serde_json::json!({ key: [one.clone()] }));
+                docs.push(serde_json::json!({ "dashboard": { key: [one.clone()] } }));
+                docs.push(serde_json::json!({ "frames": [{ "schema": { key: [one.clone()] } }] }));
+                docs.push(one);
+            }
+        }
+        for doc in &docs {
+            let _ = decode::<Dashboard>(doc).map(|d| {
+                d.queried_panels().iter().for_each(|p| drop(p.targets()));
+            });
+            let _ = decode::<QueryResponse>(doc);
+            let _ = decode::<QueryResult>(doc).map(|r| {
+                r.frames.iter().for_each(|f| drop((f.times(), f.series())));
+            });
+            let _ = decode::<Frame>(doc);
+            let _ = decode::<Field>(doc);
+            let _ = decode::<Panel>(doc);
+            let _ = decode::<Variable>(doc);
+            let _ = decode::<SearchHit>(doc);
+            let _ = decode::<Folder>(doc);
+            let _ = decode::<Datasource>(doc);
+            let _ = decode::<Org>(doc);
+            let _ = decode::<Notice>(doc);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+// drawing one panel of a dashboard, for show, render, watch and query. the
+// panel's query runs over the range and the chart is printed a row per
+// interval, then with -f new rows are added as the range moves on.
+
+use std::io::Write as _;
+
+use graf::client::{Client, Tls};
+use graf::color::{self, series_colors, series_rgb};
+use graf::follow::{Follow, Next};
+use graf::graphics::{self, Protocol};
+use graf::models::Dashboard;
+use graf::plot::Plot;
+use graf::query::{build_query, get_values, panel_time, template_value, variable_options, Query};
+use graf::raster::{self, Theme};
+use graf::render::{Chart, Frames};
+use graf::svg;
+use graf::table::{Format, Table};
+use graf::time::{datetime, parse_duration, parse_instant, parse_instant_end};
+use graf::tz::Tz;
+
+use crate::term::Caps;
+use crate::{
+    pick, piped, resize_pending, resized, select, select_one, tui, watch_resize, winsize, Command,
+    Error,
+};
+
+pub struct Options {
+    pub command: Command,
+    pub url: String,
+    pub tls: Tls,
+    // grafana live's websocket is opened without curl, so it gets the headers
+    pub live_headers: Vec<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<i64>,
+    pub tz: Option<Tz>,
+    pub compare: Option<i64>,
+    pub follow: bool,
+    pub tui: bool,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    pub color: color::Mode,
+    // print rows in this format rather than drawing them
+    pub output: Option<Format>,
+    // also draw the panel into this svg file
+    pub svg: Option<String>,
+    // draw the chart as an image the terminal shows, rather than as text
+    pub graphics: Option<(Protocol, Caps)>,
+    pub debug: usize,
+}
+
+// about the size of a panel in grafana
+const SVG_WIDTH: u32 = 900;
+const SVG_HEIGHT: u32 = 360;
+
+pub fn run(
+    client: &Client,
+    dash: &Dashboard,
+    panel: Option<&str>,
+    opts: Options,
+) -> Result<(), Error> {
+    let Options {
+        command,
+        url,
+        tls,
+        live_headers,
+        from,
+        to,
+        interval,
+        tz,
+        compare,
+        follow: mut following,
+        tui,
+        width,
+        height,
+        color,
+        output,
+        svg,
+        graphics,
+        debug,
+    } = opts;
+    let now = || {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        i64::try_from(now).unwrap()
+    };
+    let start = now();
+    // grafana shows the dashboard in its own timezone unless the viewer overrides it
+    let tz = tz.unwrap_or_else(|| {
+        let name = dash.timezone.as_deref().unwrap_or("browser");
+        Tz::named(name).unwrap_or_else(|err| {
+            eprintln!("warning: dashboard timezone: {err}, using local time");
+            Tz::local()
+        })
+    });
+    if debug > 1 {
+        eprintln!("timezone: {}", tz.name());
+    }
+
+    // default to the range and refresh the dashboard was saved with
+    const TS_ERRSTR: &str = "valid values for FROM/TO are RFC 3339 datetime '2016-02-01T13:04:05+01:00', 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD' UTC, condensed ISO 8601 UTC '20160201T130405', grafana relative 'now-5m' or 'now-1d/d', or unix epoch seconds '1678864718' or milliseconds '1678864718000'";
+    let from_s = from.as_deref().or(dash.from.as_deref()).unwrap_or("now-5m");
+    let from = parse_instant(from_s, start, &tz).ok_or(Error::new(1, TS_ERRSTR))?;
+    let to_s = to.as_deref().or(dash.to.as_deref()).unwrap_or("now");
+    if following && to_s != "now" {
+        if command == Command::Watch {
+            return Err(Error::new(1, "watch is only supported for --to now"));
+        }
+        eprintln!("error: -f is only supported for --to now, disabling follow");
+        following = false;
+    }
+    let to = parse_instant_end(to_s, start, &tz).ok_or(Error::new(1, TS_ERRSTR))?;
+    let refresh = (dash.refresh.as_deref())
+        .and_then(parse_duration)
+        .filter(|&r| r > 0);
+    let interval = match (interval, refresh) {
+        (Some(i), _) => Some(i),
+        (None, Some(r)) if following => Some(r),
+        _ => None,
+    };
+
+    let panels = dash.queried_panels();
+    if panels.is_empty() {
+        return Err(Error::new(1, "the dashboard has no panels with queries"));
+    }
+    let labels: Vec<_> = panels
+        .iter()
+        .map(|p| pick::label(&[("title", p.title.clone().into())]))
+        .collect();
+    let k = match panel {
+        // by its title, or its index in `graf ls panels`
+        Some(panel) => match panels.iter().position(|p| p.title == panel) {
+            Some(k) => k,
+            None => match panel.parse::<usize>() {
+                Ok(k) if k < panels.len() => k,
+                _ => {
+                    return Err(Error::new(
+                        6,
+                        format!("no panel {panel:?} on the dashboard"),
+                    ))
+                }
+            },
+        },
+        None if tui => 0,
+        None => select_one("a panel", &labels, debug),
+    };
+
+    if tui {
+        let opts = tui::Options {
+            from: from_s,
+            to: to_s,
+            interval,
+            compare,
+            refresh: if following {
+                interval.or(refresh)
+            } else {
+                None
+            },
+            tz: &tz,
+            color,
+            panel: k,
+        };
+        return tui::run(client, dash, opts).map_err(|err| Error::new(1, err));
+    }
+
+    let panel = panels[k];
+    let (mut rows, mut cols) = winsize(width, height);
+    if debug > 1 {
+        eprintln!("rows:{rows} cols:{cols}");
+    }
+    // a picture is as wide as the terminal and leaves a few lines of it
+    let picture = graphics.map(|(protocol, caps)| {
+        let (cell_width, cell_height) = caps.cell.unwrap_or((10, 20));
+        let width = u32::from(cols) * u32::from(cell_width);
+        let height = u32::from(rows.saturating_sub(3).max(8)) * u32::from(cell_height);
+        (protocol, caps, width, height)
+    });
+    let requested = interval;
+    // a row for each data point, or like grafana a pixel for each
+    let points = picture
+        .as_ref()
+        .map_or(i64::from(rows), |&(_, _, width, _)| i64::from(width));
+    let pt = panel_time(panel, from, to, start, points, &tz);
+    for warning in &pt.warnings {
+        eprintln!("warning: {warning}");
+    }
+    let (from, to, shift) = (pt.from, pt.to, pt.shift);
+    let interval = pt.interval(requested);
+    if debug > 1 {
+        eprintln!(
+            "from:{from} to:{to} shift:{shift} interval:{interval} refresh:{refresh:?} max_data_points:{}",
+            pt.max_data_points
+        );
+    }
+    let mut templating = std::collections::HashMap::new();
+    for var in &dash.variables {
+        let name = &var.name;
+        if command != Command::Query && output.is_none() {
+            match var.query.as_str() {
+                Some(query) => println!("templating: name={name} query={query}"),
+                None => println!("templating: name={name} query={}", var.query),
+            }
+        }
+        let vs = variable_options(client, var, from, to).unwrap_or_else(|err| {
+            eprintln!("warning: unable to look up the values of ${name}: {err}");
+            vec![]
+        });
+        let picked: Vec<_> = select(&format!("a value for ${name}"), &vs, var.multi, debug)
+            .into_iter()
+            .map(|i| vs[i].clone())
+            .collect();
+        templating.insert(name.to_string(), template_value(&picked));
+    }
+
+    let Some(targets) = panel.targets() else {
+        let message = format!("panel {:?} has no targets to query", panel.title);
+        return Err(Error::new(1, message));
+    };
+    let labels: Vec<_> = targets
+        .iter()
+        .map(|t| pick::label(&[("refId", t.ref_id.clone().into())]))
+        .collect();
+    let target = targets[select_one("a target", &labels, debug)].clone();
+    let query = Query {
+        client,
+        query: build_query(
+            client,
+            panel,
+            &target,
+            &templating,
+            pt.max_data_points,
+            interval,
+        )?,
+        refid: target.ref_id.clone(),
+        interval,
+        compare,
+    };
+
+    // the frames as grafana returned them
+    if command == Command::Query && output.is_none() {
+        let res = get_values(client, &query.query, from, to)?;
+        let res = &res["results"][&query.refid];
+        let res_s = serde_json::to_string_pretty(res).unwrap();
+        piped(writeln!(std::io::stdout(), "{res_s}"))?;
+        if let Some(err) = res["error"].as_str().filter(|e| !e.is_empty()) {
+            return Err(Error::new(
+                8,
+                format!("query {} failed: {err}", query.refid),
+            ));
+        }
+        return Ok(());
+    }
+
+    let title = &panel.title;
+    let hide_time_override = panel.hide_time_override;
+    let info = pt.info.join(", ");
+    let print_header = |from: i64, to: i64| {
+        let mut header = format!("{title} {} to {}", datetime(from, &tz), datetime(to, &tz));
+        if !info.is_empty() && !hide_time_override {
+            header.push_str(&format!(" ({info})"));
+        }
+        if let Some(compare) = compare {
+            header.push_str(&format!(
+                " compared with {} to {}",
+                datetime(from - compare, &tz),
+                datetime(to - compare, &tz)
+            ));
+        }
+        piped(writeln!(std::io::stdout(), "{header}"))
+    };
+    if output.is_none() {
+        print_header(from, to)?;
+    }
+
+    // each of the datasource's notices is printed once, not on every refresh
+    let mut noted = vec![];
+    let mut note = |notices: Vec<String>| {
+        for notice in notices {
+            if !noted.contains(&notice) {
+                eprintln!("{notice}");
+                noted.push(notice);
+            }
+        }
+    };
+    let (mut follow, fetch) = Follow::start(query, from, to, shift)?;
+    note(fetch.notices);
+    let fetched = match fetch.data {
+        Ok(Some(fetched)) => fetched,
+        Ok(None) if output.is_some() => {
+            eprintln!("no data");
+            return Ok(());
+        }
+        Ok(None) => return piped(writeln!(std::io::stdout(), "no data")),
+        Err(err) => {
+            let message = format!("query {} failed: {err}", follow.query.refid);
+            return Err(Error::new(8, message));
+        }
+    };
+    let plot = (svg.is_some() || picture.is_some()).then(|| {
+        Plot::new(
+            panel,
+            fetched.times.clone(),
+            fetched.vals.clone(),
+            fetched.ncurrent,
+            &fetched.names,
+            series_rgb(panel, &fetched.names, &fetched.colors),
+        )
+    });
+    if let (Some(path), Some(plot)) = (&svg, &plot) {
+        let written = std::fs::File::create(path).and_then(|file| {
+            let mut w = std::io::BufWriter::new(file);
+            svg::write(&mut w, plot, SVG_WIDTH, SVG_HEIGHT, &tz)?;
+            w.flush()
+        });
+        written.map_err(|err| Error::new(1, format!("unable to write {path}: {err}")))?;
+    }
+    if let (Some((protocol, caps, width, height)), Some(plot)) = (&picture, &plot) {
+        let theme = Theme {
+            fg: caps.fg.unwrap_or(Theme::DARK.fg),
+            bg: caps.bg.unwrap_or(Theme::DARK.bg),
+        };
+        // the font's 7 pixel rows about half a line high
+        let scale = caps
+            .cell
+            .map_or(1, |(_, height)| (u32::from(height) / 14).max(1));
+        let image = raster::draw(plot, *width, *height, scale, theme, &tz);
+        let mut stdout = std::io::stdout().lock();
+        return piped(
+            graphics::write(&mut stdout, *protocol, &image).and_then(|_| writeln!(stdout)),
+        );
+    }
+    // rows for other tools instead of the chart, as they come when following
+    let mut table = output.map(|format| {
+        let names = &fetched.names[..fetched.ncurrent.min(fetched.names.len())];
+        Table::new(format, &fetched.time_name, names)
+    });
+    let write_rows = |table: &mut Option<Table>, times: &[i64], vals: &Frames| match table {
+        Some(table) => piped(table.write_rows(&mut std::io::stdout().lock(), times, vals, &tz)),
+        None => Ok(()),
+    };
+    if let Some(table) = &table {
+        piped(table.write_header(&mut std::io::stdout().lock()))?;
+    }
+    write_rows(&mut table, &fetched.times, &fetched.vals)?;
+    // make room for time stamps "13:04:05 "
+    let mut chart = Chart::new(
+        fetched.times,
+        fetched.vals,
+        fetched.ncurrent,
+        cols - 9,
+        vec![],
+        series_colors(color, panel, &fetched.names, &fetched.colors),
+    );
+    if debug > 1 {
+        let (min, max) = (chart.min, chart.max);
+        let log_base = (max - min).log10();
+        eprintln!("log_base:{log_base} min:{min} max:{max} cols:{cols}");
+    }
+
+    // follow streaming datasources over grafana live rather than polling
+    if following {
+        if let Err(err) = follow.stream(&url, &live_headers, &tls) {
+            eprintln!("warning: {err}, polling instead");
+        }
+    }
+
+    // rows are only redrawn for a new size, the table has none
+    if following && table.is_none() {
+        watch_resize();
+    }
+    let mut i0 = 0;
+    // whether the chart's rows have been written
+    let mut drawn = false;
+    loop {
+        // start over with the new size, so the header and scale fit again.
+        // the interval follows the size too unless it was given
+        if resized() {
+            (rows, cols) = winsize(width, height);
+            // relative times are from now, not from when we started
+            let now = now();
+            let from = parse_instant(from_s, now, &tz).ok_or(Error::new(1, TS_ERRSTR))?;
+            let to = parse_instant_end(to_s, now, &tz).ok_or(Error::new(1, TS_ERRSTR))?;
+            let pt = panel_time(panel, from, to, now, i64::from(rows), &tz);
+            let interval = pt.interval(requested);
+            follow.query.query = build_query(
+                client,
+                panel,
+                &target,
+                &templating,
+                pt.max_data_points,
+                interval,
+            )?;
+            follow.query.interval = interval;
+            if debug > 1 {
+                eprintln!("rows:{rows} cols:{cols} interval:{interval}");
+            }
+            let fetch = follow.restart(pt.to - pt.from)?;
+            print_header(follow.from, follow.to)?;
+            note(fetch.notices);
+            match fetch.data {
+                Ok(Some(fetched)) => {
+                    chart = Chart::new(
+                        fetched.times,
+                        fetched.vals,
+                        fetched.ncurrent,
+                        cols - 9,
+                        vec![],
+                        series_colors(color, panel, &fetched.names, &fetched.colors),
+                    );
+                    (i0, drawn) = (0, false);
+                }
+                Ok(None) => piped(writeln!(std::io::stdout(), "no data"))?,
+                Err(err) => eprintln!("error: query {} failed: {err}", follow.query.refid),
+            }
+        }
+
+        if table.is_none() && !drawn {
+            let mut stdout = std::io::stdout().lock();
+            for i in 1..chart.times.len() {
+                let header = (i0 + i) % usize::from(rows) == 1;
+                let label = (i0 + i) % 5 == 1;
+                piped(
+                    (chart.write_row(&mut stdout, i, header, label, &tz))
+                        .and_then(|_| writeln!(stdout)),
+                )?;
+            }
+            i0 += chart.times.len() - 1;
+        }
+        drawn = true;
+
+        if !following {
+            return Ok(());
+        }
+
+        let mut notices = vec![];
+        let next = follow.next(&resize_pending, &mut notices)?;
+        note(notices);
+        match next {
+            Next::Rows {
+                times,
+                vals,
+                ncurrent,
+            } => {
+                write_rows(&mut table, &times, &vals)?;
+                chart.update(times, vals, ncurrent);
+                drawn = false;
+            }
+            Next::NoData if table.is_none() => piped(writeln!(std::io::stdout(), "no data"))?,
+            Next::NoData | Next::Interrupted => {}
+            Next::Failed(err) => eprintln!("error: query {} failed: {err}", follow.query.refid),
+            Next::Polling(err) => eprintln!("warning: {err}, polling instead"),
+        }
+    }
+}
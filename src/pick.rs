@@ -0,0 +1,267 @@
+// choosing dashboards, panels and variable values. on a terminal this is a
+// type to filter fuzzy finder, otherwise choices are listed and read back as
+// an index or a piece of the label. everything is drawn on stderr so stdout
+// only gets the chart.
+
+use std::io::Write as _;
+
+use crate::term::{read_key, winsize, Key, Term};
+
+/// a label from `fields`, like `title="cpu" uid="abc"`, leaving out the
+/// ones that are null or empty
+pub fn label(fields: &[(&str, serde_json::Value)]) -> String {
+    fields
+        .iter()
+        .filter(|(_, v)| !v.is_null() && v.as_array().is_none_or(|a| !a.is_empty()))
+        .map(|(key, v)| format!("{key}={v}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// asks for one of `labels`, or any number of them if `multi`, as indexes
+/// into `labels`. None if there was nothing to pick from or the user gave up
+pub fn pick(what: &str, labels: &[String], multi: bool) -> Option<Vec<usize>> {
+    match labels.len() {
+        0 => None,
+        1 => Some(vec![0]),
+        _ if crate::term::isatty(2) => match Term::inline() {
+            Ok(term) => find(what, labels, multi, term),
+            Err(_) => ask(what, labels, multi),
+        },
+        _ => ask(what, labels, multi),
+    }
+}
+
+// lists the choices and reads back answers, an index or a piece of a label,
+// comma separated for `multi`
+fn ask(what: &str, labels: &[String], multi: bool) -> Option<Vec<usize>> {
+    for (i, label) in labels.iter().enumerate() {
+        eprintln!("{i} - {label}");
+    }
+    let mut buf = String::new();
+    'ask: loop {
+        eprint!("Please select {what}: ");
+        buf.clear();
+        if std::io::stdin().read_line(&mut buf).ok()? == 0 {
+            eprintln!();
+            return None;
+        }
+        let answers: Vec<_> = if multi {
+            buf.split(',').map(str::trim).collect()
+        } else {
+            vec![buf.trim()]
+        };
+        let mut picked = vec![];
+        for answer in answers.into_iter().filter(|a| !a.is_empty()) {
+            match resolve(answer, labels) {
+                Ok(i) => picked.push(i),
+                Err(matches) if matches.is_empty() => {
+                    eprintln!("nothing matches {answer:?}");
+                    continue 'ask;
+                }
+                Err(matches) => {
+                    for i in matches {
+                        eprintln!("{i} - {}", labels[i]);
+                    }
+                    continue 'ask;
+                }
+            }
+        }
+        if !picked.is_empty() {
+            return Some(picked);
+        }
+    }
+}
+
+// the one label `answer` picks, or all of the labels it could mean
+fn resolve(answer: &str, labels: &[String]) -> Result<usize, Vec<usize>> {
+    if let Some(i) = answer.parse::<usize>().ok().filter(|&i| i < labels.len()) {
+        return Ok(i);
+    }
+    let answer = answer.to_lowercase();
+    let matches: Vec<_> = (0..labels.len())
+        .filter(|&i| labels[i].to_lowercase().contains(&answer))
+        .collect();
+    match matches[..] {
+        [i] => Ok(i),
+        _ => Err(matches),
+    }
+}
+
+// how well `pattern` matches `text`, each word of the pattern has to appear
+// in order in the text, runs of characters and word starts count more
+fn score(pattern: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    for word in pattern.to_lowercase().split_whitespace() {
+        let (mut from, mut last) = (0, None);
+        for c in word.chars() {
+            let k = from + text[from..].iter().position(|&t| t == c)?;
+            score += 1;
+            if last.is_some_and(|l| l + 1 == k) {
+                score += 4;
+            }
+            if k == 0 || !text[k - 1].is_alphanumeric() {
+                score += 2;
+            }
+            (from, last) = (k + 1, Some(k));
+        }
+    }
+    Some(score)
+}
+
+// indexes of the labels matching `pattern`, best first. a pattern that is an
+// index puts that label first
+fn filter(pattern: &str, labels: &[String]) -> Vec<usize> {
+    let index = pattern
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&i| i < labels.len());
+    let mut scored: Vec<_> = labels
+        .iter()
+        .enumerate()
+        .filter_map(|(i, label)| Some((score(pattern, label)?, i)))
+        .filter(|&(_, i)| Some(i) != index)
+        .collect();
+    scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+    index
+        .into_iter()
+        .chain(scored.into_iter().map(|(_, i)| i))
+        .collect()
+}
+
+fn find(what: &str, labels: &[String], multi: bool, term: Term) -> Option<Vec<usize>> {
+    let (rows, _) = winsize(2);
+    let height = usize::from(rows - 2).min(10);
+    let mut pattern = String::new();
+    let (mut cursor, mut top) = (0, 0);
+    let mut marked = vec![false; labels.len()];
+    let mut stderr = std::io::stderr().lock();
+    let picked = loop {
+        let matches = filter(&pattern, labels);
+        cursor = cursor.min(matches.len().saturating_sub(1));
+        // keep the cursor in view
+        top = top.min(cursor).max((cursor + 1).saturating_sub(height));
+
+        let mut out = vec![];
+        let prompt = format!("Please select {what}: {pattern}");
+        let _ = write!(out, "\r\x1b[J{prompt}");
+        let _ = write!(out, "  \x1b[2m{}/{}", matches.len(), labels.len());
+        if multi {
+            let _ = write!(out, "  tab to mark");
+        }
+        let _ = write!(out, "\x1b[0m");
+        let shown = &matches[top..matches.len().min(top + height)];
+        for (k, &i) in shown.iter().enumerate() {
+            let mark = if marked[i] { '*' } else { ' ' };
+            let label = &labels[i];
+            if top + k == cursor {
+                let _ = write!(out, "\n\x1b[7m{mark}{i} - {label}\x1b[0m");
+            } else {
+                let _ = write!(out, "\n{mark}{i} - {label}");
+            }
+        }
+        // back to the end of the prompt
+        if !shown.is_empty() {
+            let _ = write!(out, "\x1b[{}A", shown.len());
+        }
+        let _ = write!(out, "\r\x1b[{}C", prompt.chars().count());
+        let _ = stderr.write_all(&out);
+        let _ = stderr.flush();
+
+        let key = loop {
+            if let Some(key) = read_key() {
+                break key;
+            }
+        };
+        match key {
+            Key::Esc | Key::Ctrl('c' | 'd') => break None,
+            Key::Enter => {
+                let picked: Vec<_> = (0..labels.len()).filter(|&i| marked[i]).collect();
+                match (picked.is_empty(), matches.get(cursor)) {
+                    (false, _) => break Some(picked),
+                    (true, Some(&i)) => break Some(vec![i]),
+                    (true, None) => {}
+                }
+            }
+            Key::Up | Key::Ctrl('p') => cursor = cursor.saturating_sub(1),
+            Key::Down | Key::Ctrl('n') => cursor = (cursor + 1).min(matches.len().max(1) - 1),
+            Key::Tab if multi => {
+                if let Some(&i) = matches.get(cursor) {
+                    marked[i] = !marked[i];
+                    cursor += 1;
+                }
+            }
+            Key::Backspace => {
+                pattern.pop();
+                (cursor, top) = (0, 0);
+            }
+            Key::Ctrl('u') => {
+                pattern.clear();
+                (cursor, top) = (0, 0);
+            }
+            Key::Char(c) if !c.is_control() => {
+                pattern.push(c);
+                (cursor, top) = (0, 0);
+            }
+            _ => {}
+        }
+    };
+
+    // leave just the answer behind
+    let _ = write!(stderr, "\r\x1b[J");
+    drop(term);
+    match &picked {
+        Some(picked) => {
+            let picked: Vec<_> = picked.iter().map(|&i| labels[i].as_str()).collect();
+            let _ = writeln!(stderr, "Please select {what}: {}", picked.join(", "));
+        }
+        None => {
+            let _ = writeln!(stderr, "Please select {what}:");
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(titles: &[&str]) -> Vec<String> {
+        (titles.iter())
+            .map(|t| label(&[("title", (*t).into())]))
+            .collect()
+    }
+
+    #[test]
+    fn ranking() {
+        let labels = labels(&["cache pull ups", "memory", "cpu usage", "CPU"]);
+        // runs of characters and word starts first, ties in order
+        assert_eq!(filter("cpu", &labels), [2, 3, 0]);
+        assert_eq!(filter("cpu use", &labels), [2]);
+        // every word has to match, each in order
+        assert_eq!(filter("mem cpu", &labels), Vec::<usize>::new());
+        assert_eq!(score("upc", "cpu"), None);
+        assert!(score("cpu", "cpu") > score("cpu", "c p u"));
+        assert!(score("u", "usage") > score("u", "cpu"));
+        // nothing typed yet lists them all
+        assert_eq!(filter("", &labels), [0, 1, 2, 3]);
+        // an index goes first, then the labels it's in
+        assert_eq!(filter("1", &labels), [1]);
+        assert_eq!(filter(" 3 ", &labels), [3]);
+    }
+
+    #[test]
+    fn answers() {
+        let labels = labels(&["cpu", "memory", "port 4242", "cpu idle"]);
+        assert_eq!(resolve("1", &labels), Ok(1));
+        assert_eq!(resolve("MEM", &labels), Ok(1));
+        // ambiguous, or nothing at all
+        assert_eq!(resolve("cpu", &labels), Err(vec![0, 3]));
+        assert_eq!(resolve("disk", &labels), Err(vec![]));
+        // past the end, a number is looked for in the labels
+        assert_eq!(resolve("42", &labels), Ok(2));
+        assert_eq!(resolve("7", &labels), Err(vec![]));
+    }
+}
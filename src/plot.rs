@@ -0,0 +1,489 @@
+// a panel's series as a picture, in pixels rather than rows of text: where
+// the plot area, the ticks of the axes and the legend go, and the values in
+// the panel's unit. svg and raster draw it.
+
+use crate::models::Panel;
+use crate::render::Frames;
+use crate::time::datetime;
+use crate::tz::Tz;
+
+pub struct Plot {
+    pub title: String,
+    pub times: Vec<i64>,
+    // every series of every frame, compared series after the `ncurrent`
+    // current ones
+    pub series: Vec<Vec<Option<f64>>>,
+    pub ncurrent: usize,
+    pub names: Vec<String>,
+    // 0xrrggbb of each current series
+    pub colors: Vec<u32>,
+    // grafana's unit for the values, like 'bytes' or 'percent'
+    pub unit: String,
+    pub decimals: Option<usize>,
+}
+
+impl Plot {
+    /// the values of `vals` at `times`, with the panel's title and unit
+    pub fn new(
+        panel: &Panel,
+        times: Vec<i64>,
+        vals: Frames,
+        ncurrent: usize,
+        names: &[String],
+        colors: Vec<u32>,
+    ) -> Plot {
+        let defaults = &panel.field_config["defaults"];
+        Plot {
+            title: panel.title.clone(),
+            times,
+            series: vals.into_iter().flatten().collect(),
+            ncurrent,
+            names: names.to_vec(),
+            colors,
+            unit: defaults["unit"].as_str().unwrap_or_default().to_string(),
+            decimals: (defaults["decimals"].as_u64()).and_then(|d| usize::try_from(d).ok()),
+        }
+    }
+
+    /// the color of series `k`, and whether it's a compared series, which is
+    /// drawn faint in the color of its current series
+    pub fn color(&self, k: usize) -> (u32, bool) {
+        let (k, compared) = match k.checked_sub(self.ncurrent) {
+            Some(k) => (k, true),
+            None => (k, false),
+        };
+        (self.colors.get(k).copied().unwrap_or(0x808080), compared)
+    }
+}
+
+/// where everything goes on a `width` x `height` picture
+pub struct Layout {
+    pub width: f64,
+    pub height: f64,
+    // the height of text
+    pub font: f64,
+    // the edges of the plot area
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+    // the times (ms) and values at the edges
+    from: i64,
+    to: i64,
+    min: f64,
+    max: f64,
+    // 'FROM to TO', for next to the title
+    pub range: String,
+    // the y of each value tick and the x of each time tick, with its label
+    pub yticks: Vec<(f64, String)>,
+    pub xticks: Vec<(f64, String)>,
+    // the left edge and baseline of each current series' legend entry, as
+    // many as fit
+    pub legend: Vec<(f64, f64)>,
+}
+
+// text is about this wide for its height, in a sans-serif font
+const ASPECT: f64 = 0.6;
+
+// time ticks step by one of these, in seconds
+const TIME_STEPS: [i64; 22] = [
+    1,
+    2,
+    5,
+    10,
+    15,
+    30,
+    60,
+    2 * 60,
+    5 * 60,
+    10 * 60,
+    15 * 60,
+    30 * 60,
+    60 * 60,
+    2 * 60 * 60,
+    3 * 60 * 60,
+    6 * 60 * 60,
+    12 * 60 * 60,
+    24 * 60 * 60,
+    2 * 24 * 60 * 60,
+    7 * 24 * 60 * 60,
+    14 * 24 * 60 * 60,
+    30 * 24 * 60 * 60,
+];
+
+// a round step of 1, 2 or 5 times a power of ten, about `rough`
+fn nice_step(rough: f64) -> f64 {
+    let pow = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .find(|&m| m * pow >= rough)
+        .unwrap_or(10.0);
+    step * pow
+}
+
+impl Layout {
+    /// lays out `plot` with text `font` pixels high, times in `tz`
+    pub fn new(plot: &Plot, width: u32, height: u32, font: f64, tz: &Tz) -> Layout {
+        let (width, height) = (f64::from(width), f64::from(height));
+        let text_width = |text: &str| text.chars().count() as f64 * font * ASPECT;
+        let pad = font;
+
+        // the legend's rows take what they need from the bottom, up to a third
+        let row = font * 1.6;
+        let swatch = font * 1.5;
+        let max_rows = ((height / 3.0 / row).floor() as usize).max(1);
+        let (mut x, mut rows) = (pad, 1);
+        let mut legend = vec![];
+        for name in plot.names.iter().take(plot.ncurrent) {
+            let entry = swatch + font * 0.5 + text_width(name);
+            if x > pad && x + entry > width - pad {
+                x = pad;
+                rows += 1;
+            }
+            if rows > max_rows {
+                break;
+            }
+            legend.push((x, rows as f64));
+            x += entry + font * 1.5;
+        }
+        let rows = if legend.is_empty() {
+            0
+        } else {
+            rows.min(max_rows)
+        };
+        let legend_top = height - pad - rows as f64 * row;
+        let legend = legend
+            .into_iter()
+            .map(|(x, r)| (x, legend_top + r * row - (row - font) / 2.0))
+            .collect();
+
+        // values, the scale rounded out to whole steps
+        let finite = plot
+            .series
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|v| v.is_finite());
+        let (mut min, mut max) = finite.fold((f64::INFINITY, -f64::INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+        if min > max {
+            (min, max) = (0.0, 1.0);
+        } else if min == max {
+            let pad = if min == 0.0 { 1.0 } else { min.abs() / 10.0 };
+            (min, max) = (min - pad, max + pad);
+        }
+        let top = pad + font * 2.0;
+        let bottom = legend_top - font * 2.0;
+        let ticks = ((bottom - top) / (font * 3.0)).floor().max(2.0);
+        let step = nice_step((max - min) / ticks);
+        (min, max) = ((min / step).floor() * step, (max / step).ceil() * step);
+        let values: Vec<_> = (0..)
+            .map(|i| min + step * f64::from(i))
+            .take_while(|&v| v <= max + step / 2.0)
+            .collect();
+        // the fewest decimals that tell the ticks apart, unless the panel says.
+        // the ticks of values too small for any are labeled all the same
+        let label = |d| {
+            let decimals = plot.decimals.or(Some(d));
+            (values.iter())
+                .map(|&v| format_value(v, &plot.unit, decimals))
+                .collect::<Vec<_>>()
+        };
+        let labels = (0..=15)
+            .map(label)
+            .find(|labels| plot.decimals.is_some() || labels.windows(2).all(|l| l[0] != l[1]))
+            .unwrap_or_else(|| label(15));
+        let left = pad + labels.iter().map(|l| text_width(l)).fold(0.0, f64::max) + font * 0.5;
+        let right = width - pad;
+
+        let from = plot.times.first().copied().unwrap_or(0);
+        let to = plot.times.last().copied().unwrap_or(0).max(from + 1000);
+        let mut layout = Layout {
+            width,
+            height,
+            font,
+            left,
+            right,
+            top,
+            bottom,
+            from,
+            to,
+            min,
+            max,
+            range: format!(
+                "{} to {}",
+                datetime(from / 1000, tz),
+                datetime(to / 1000, tz)
+            ),
+            yticks: vec![],
+            xticks: vec![],
+            legend,
+        };
+        layout.yticks = values
+            .iter()
+            .zip(labels)
+            .map(|(&v, label)| (layout.y(v), label))
+            .collect();
+
+        // times, at whole steps in `tz` with room for their labels
+        let span = (to - from) / 1000;
+        let format = |step: i64| -> (usize, usize) {
+            // the part of 'YYYY-MM-DD HH:MM:SS' to show
+            if step < 60 {
+                (11, 19)
+            } else if span <= 24 * 60 * 60 {
+                (11, 16)
+            } else if step < 24 * 60 * 60 {
+                (5, 16)
+            } else {
+                (0, 10)
+            }
+        };
+        let step = TIME_STEPS
+            .into_iter()
+            .find(|&step| {
+                let (a, b) = format(step);
+                let label = (b - a) as f64 * font * ASPECT + font * 2.0;
+                span as f64 / step as f64 * label <= right - left
+            })
+            .unwrap_or(TIME_STEPS[TIME_STEPS.len() - 1]);
+        let (a, b) = format(step);
+        let offset = tz.offset(from / 1000);
+        let first = ((from / 1000 + offset) as f64 / step as f64).ceil() as i64 * step - offset;
+        layout.xticks = (0..)
+            .map(|i| first + step * i)
+            .take_while(|&t| t * 1000 <= to)
+            .map(|t| (layout.x(t * 1000), datetime(t, tz)[a..b].to_string()))
+            // labels centered on the ticks at the ends may not fit
+            .filter(|(x, label)| {
+                let half = text_width(label) / 2.0;
+                x - half >= 0.0 && x + half <= width
+            })
+            .collect();
+        layout
+    }
+
+    /// the x of the time `time` in ms
+    pub fn x(&self, time: i64) -> f64 {
+        let at = (time - self.from) as f64 / (self.to - self.from) as f64;
+        self.left + at * (self.right - self.left)
+    }
+
+    /// the y of the value `v`
+    pub fn y(&self, v: f64) -> f64 {
+        let at = (v - self.min) / (self.max - self.min);
+        self.bottom - at * (self.bottom - self.top)
+    }
+
+    /// the points of each series, in runs between missing values
+    pub fn lines(&self, plot: &Plot) -> Vec<Vec<Vec<(f64, f64)>>> {
+        plot.series
+            .iter()
+            .map(|vs| {
+                let mut runs = vec![];
+                let mut run = vec![];
+                for (&time, v) in plot.times.iter().zip(vs) {
+                    match v.filter(|v| v.is_finite()) {
+                        Some(v) => run.push((self.x(time), self.y(v))),
+                        None if !run.is_empty() => runs.push(std::mem::take(&mut run)),
+                        None => {}
+                    }
+                }
+                if !run.is_empty() {
+                    runs.push(run);
+                }
+                runs
+            })
+            .collect()
+    }
+}
+
+// `v` with `decimals`, or up to three significant digits
+fn number(v: f64, decimals: Option<usize>) -> String {
+    let s = match decimals {
+        Some(d) => format!("{v:.d$}"),
+        None => {
+            let magnitude = if v == 0.0 {
+                0.0
+            } else {
+                v.abs().log10().floor()
+            };
+            let d = (2.0 - magnitude).clamp(0.0, 9.0) as usize;
+            let s = format!("{v:.d$}");
+            if s.contains('.') {
+                s.trim_end_matches('0').trim_end_matches('.').to_string()
+            } else {
+                s
+            }
+        }
+    };
+    match s.trim_start_matches('-').trim_matches(['0', '.']) {
+        "" => s.trim_start_matches('-').to_string(),
+        _ => s,
+    }
+}
+
+/// `v` in grafana's `unit`, like 'percent', 'bytes' or 'ms', scaled to
+/// kilo- and megabytes and such. unknown units are written after the value
+pub fn format_value(v: f64, unit: &str, decimals: Option<usize>) -> String {
+    let num = |v: f64| number(v, decimals);
+    let scaled = |v: f64, base: f64, suffixes: &[&str]| {
+        let (mut v, mut k) = (v, 0);
+        while v.abs() >= base && k + 1 < suffixes.len() {
+            v /= base;
+            k += 1;
+        }
+        format!("{}{}", num(v), suffixes[k])
+    };
+    let duration = |secs: f64| {
+        const UNITS: [(f64, &str); 7] = [
+            (86400.0, " day"),
+            (3600.0, " hour"),
+            (60.0, " min"),
+            (1.0, " s"),
+            (1e-3, " ms"),
+            (1e-6, " µs"),
+            (1e-9, " ns"),
+        ];
+        let (scale, name) = UNITS
+            .into_iter()
+            .find(|&(scale, _)| secs.abs() >= scale || (secs == 0.0 && scale == 1.0))
+            .unwrap_or(UNITS[6]);
+        format!("{}{name}", num(secs / scale))
+    };
+    match unit {
+        "" | "none" => num(v),
+        "short" => scaled(v, 1000.0, &["", " K", " Mil", " Bil", " Tri"]),
+        "percent" => format!("{}%", num(v)),
+        "percentunit" => format!("{}%", num(v * 100.0)),
+        "bytes" => scaled(v, 1024.0, &[" B", " KiB", " MiB", " GiB", " TiB", " PiB"]),
+        "decbytes" => scaled(v, 1000.0, &[" B", " kB", " MB", " GB", " TB", " PB"]),
+        "bits" => scaled(v, 1024.0, &[" b", " Kib", " Mib", " Gib", " Tib", " Pib"]),
+        "decbits" => scaled(v, 1000.0, &[" b", " kb", " Mb", " Gb", " Tb", " Pb"]),
+        "Bps" => scaled(v, 1000.0, &[" B/s", " kB/s", " MB/s", " GB/s", " TB/s"]),
+        "binBps" => scaled(v, 1024.0, &[" B/s", " KiB/s", " MiB/s", " GiB/s", " TiB/s"]),
+        "bps" => scaled(v, 1000.0, &[" b/s", " kb/s", " Mb/s", " Gb/s", " Tb/s"]),
+        "hertz" => scaled(v, 1000.0, &[" Hz", " kHz", " MHz", " GHz"]),
+        "ns" => duration(v * 1e-9),
+        "µs" => duration(v * 1e-6),
+        "ms" => duration(v * 1e-3),
+        "s" => duration(v),
+        "m" => duration(v * 60.0),
+        "h" => duration(v * 3600.0),
+        "d" => duration(v * 86400.0),
+        "celsius" => format!("{}°C", num(v)),
+        "fahrenheit" => format!("{}°F", num(v)),
+        "reqps" => format!("{} req/s", num(v)),
+        "ops" => format!("{} ops/s", num(v)),
+        _ => match unit.split_once(':') {
+            Some(("suffix", suffix)) => format!("{}{suffix}", num(v)),
+            Some(("prefix", prefix)) => format!("{prefix}{}", num(v)),
+            _ => format!("{} {unit}", num(v)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plot(series: Vec<Vec<Option<f64>>>, unit: &str) -> Plot {
+        let times = (0..series[0].len() as i64).map(|i| i * 60_000).collect();
+        Plot {
+            title: "cpu".to_string(),
+            times,
+            ncurrent: series.len(),
+            names: (0..series.len()).map(|k| format!("s{k}")).collect(),
+            colors: vec![],
+            series,
+            unit: unit.to_string(),
+            decimals: None,
+        }
+    }
+
+    #[test]
+    fn nice_steps() {
+        for (rough, step) in [
+            (0.3, 0.5),
+            (1.0, 1.0),
+            (1.1, 2.0),
+            (7.0, 10.0),
+            (0.0012, 0.002),
+            (1234.0, 2000.0),
+            (3e12, 5e12),
+        ] {
+            let got = nice_step(rough);
+            assert!((got - step).abs() < step * 1e-9, "{rough}: {got}");
+        }
+    }
+
+    #[test]
+    fn values() {
+        for (v, unit, s) in [
+            (0.0, "", "0"),
+            (-1234.0, "", "-1234"),
+            (-0.5, "percent", "-0.5%"),
+            (0.000123, "", "0.000123"),
+            // too small to show isn't negative zero
+            (-1e-12, "", "0"),
+            (1.5e15, "", "1500000000000000"),
+            (1.5e15, "short", "1500 Tri"),
+            (-2500.0, "short", "-2.5 K"),
+            (1.5 * 1024.0 * 1024.0 * 1024.0, "bytes", "1.5 GiB"),
+            (0.25, "percentunit", "25%"),
+            (90_000.0, "ms", "1.5 min"),
+            (0.0005, "s", "500 µs"),
+            (3.0, "suffix: apples", "3 apples"),
+            (3.0, "widgets", "3 widgets"),
+        ] {
+            assert_eq!(format_value(v, unit, None), s, "{v} {unit}");
+        }
+        assert_eq!(format_value(2.0, "percent", Some(2)), "2.00%");
+        assert_eq!(format_value(-0.001, "", Some(1)), "0.0");
+    }
+
+    #[test]
+    fn ticks() {
+        let l = Layout::new(
+            &plot(vec![vec![Some(-3.0), Some(12.0), None]], ""),
+            600,
+            300,
+            12.0,
+            &Tz::utc(),
+        );
+        let labels: Vec<_> = l.yticks.iter().map(|(_, label)| label.as_str()).collect();
+        assert_eq!(labels.first(), Some(&"-5"));
+        assert_eq!(labels.last(), Some(&"15"));
+        // evenly spaced, top to bottom
+        let ys: Vec<_> = l.yticks.iter().map(|&(y, _)| y).collect();
+        assert!(ys.windows(2).all(|y| y[1] < y[0]));
+        assert_eq!((ys[0], ys[ys.len() - 1]), (l.bottom, l.top));
+        let xs: Vec<_> = l.xticks.iter().map(|(_, label)| label.as_str()).collect();
+        // the last label doesn't fit centered on the right edge
+        assert_eq!(xs, ["00:00:00", "00:00:30", "00:01:00", "00:01:30"]);
+    }
+
+    #[test]
+    fn flat_series() {
+        // a flat series gets some room around it rather than a zero range
+        for v in [5.0, 0.0, -200.0, 1e-9, 1e300] {
+            let l = Layout::new(
+                &plot(vec![vec![Some(v); 3]], ""),
+                600,
+                300,
+                12.0,
+                &Tz::utc(),
+            );
+            let labels: Vec<_> = l.yticks.iter().map(|(_, label)| label.as_str()).collect();
+            assert!(labels.len() >= 3, "{v}: {labels:?}");
+            assert!(labels.windows(2).all(|l| l[0] != l[1]), "{v}: {labels:?}");
+            let y = l.y(v);
+            assert!(y > l.top && y < l.bottom, "{v}");
+        }
+        // and nothing at all gets 0 to 1
+        let l = Layout::new(&plot(vec![vec![None; 3]], ""), 600, 300, 12.0, &Tz::utc());
+        assert_eq!(l.yticks.first().map(|t| t.1.as_str()), Some("0.0"));
+        assert_eq!(l.yticks.last().map(|t| t.1.as_str()), Some("1.0"));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+
+use crate::client::{Client, Failure};
+use crate::models::{decode, Frame, Panel, QueryResponse, Target, Variable};
+use crate::render::Frames;
+use crate::time::{calculate_interval, parse_duration, parse_instant, parse_instant_end};
+use crate::tz::Tz;
+
+pub fn visit_replace(v: &mut serde_json::Value, tmpls: &HashMap<String, String>) {
+    match v {
+        serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {}
+        serde_json::Value::String(s) => {
+            for (p, to) in tmpls {
+                *s = s.replace(&format!("${p}"), to);
+            }
+        }
+        serde_json::Value::Array(elems) => {
+            for e in elems {
+                visit_replace(e, tmpls);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for v in map.values_mut() {
+                visit_replace(v, tmpls);
+            }
+        }
+    }
+}
+
+/// the values a templating variable can take in the range `from`..`to`
+pub fn variable_options(
+    client: &Client,
+    var: &Variable,
+    from: i64,
+    to: i64,
+) -> Result<Vec<String>, String> {
+    let q = serde_json::json!({
+        "queries": [{
+            "datasource": var.datasource,
+            "query": var.query,
+            "rawQuery": true,
+        }],
+
+        // XXX: this doesn't seem to actually limit it to values that existed in that window?
+        // for instance, I've restarted telegraf container, had two hostnames, and set the
+        // window so small that influx data only exists for the one host, but this query
+        // will return both host values. If I choose the older one, I just get back "no data".
+        "from": (from * 1000).to_string(),
+        "to": (to * 1000).to_string(),
+    });
+    let res = client
+        .post("/api/ds/query", &q.to_string())
+        .map_err(|failure| failure.to_string())?;
+    let frames = result_frames(&res, "A", &mut vec![])?;
+    let vs: std::collections::HashSet<_> = frames
+        .iter()
+        .flat_map(|f| f.values.iter().flatten())
+        .map(|v| match v {
+            serde_json::Value::String(s) => s.to_string(),
+            v => v.to_string(),
+        })
+        .collect();
+    let mut vs: Vec<_> = vs.into_iter().collect();
+    vs.sort();
+    Ok(vs)
+}
+
+/// the value of a variable for `picked` of its options, several of them are
+/// matched as a regex alternation
+pub fn template_value(picked: &[String]) -> String {
+    match picked {
+        [one] => one.clone(),
+        _ => format!("({})", picked.join("|")),
+    }
+}
+
+/// the time range and query options of a panel, after its overrides
+pub struct PanelTime {
+    pub from: i64,
+    pub to: i64,
+    pub shift: i64,
+    // describes the overrides, like grafana's panel header does
+    pub info: Vec<String>,
+    pub min_interval: i64,
+    pub max_data_points: i64,
+    // overrides that couldn't be parsed and were ignored
+    pub warnings: Vec<String>,
+}
+
+// panel query options override the dashboard range the same way grafana
+// applies them, relative time first then the time shift
+pub fn panel_time(
+    panel: &Panel,
+    from: i64,
+    to: i64,
+    now: i64,
+    max_data_points: i64,
+    tz: &Tz,
+) -> PanelTime {
+    let (mut from, mut to) = (from, to);
+    let mut info = vec![];
+    let mut warnings = vec![];
+    if let Some(time_from) = panel.time_from.as_deref() {
+        let (rel_from, rel_to) = match time_from {
+            t if t.starts_with("now") && t.contains('/') => (t.to_string(), t),
+            t if t.starts_with("now") => (t.to_string(), "now"),
+            t => (format!("now-{t}"), "now"),
+        };
+        match (
+            parse_instant(&rel_from, now, tz),
+            parse_instant_end(rel_to, now, tz),
+        ) {
+            (Some(f), Some(t)) => {
+                (from, to) = (f, t);
+                info.push(format!("last {}", time_from.trim_start_matches("now-")));
+            }
+            _ => warnings.push(format!(
+                "ignoring invalid panel relative time {time_from:?}"
+            )),
+        }
+    }
+    let shift = match panel.time_shift.as_deref() {
+        Some(time_shift) => match parse_duration(time_shift.trim_start_matches('-')) {
+            Some(shift) => {
+                info.push(format!("timeshift -{}", time_shift.trim_start_matches('-')));
+                shift
+            }
+            None => {
+                warnings.push(format!("ignoring invalid panel time shift {time_shift:?}"));
+                0
+            }
+        },
+        None => 0,
+    };
+    let min_interval = (panel.interval.as_deref())
+        .and_then(|i| parse_duration(i.trim_start_matches('>')))
+        .unwrap_or(0);
+    let max_data_points = panel.max_data_points.unwrap_or(max_data_points);
+    PanelTime {
+        from: from - shift,
+        to: to - shift,
+        shift,
+        info,
+        min_interval,
+        max_data_points,
+        warnings,
+    }
+}
+
+impl PanelTime {
+    /// interval in seconds for `interval` requested by the user, or else for
+    /// the range as grafana would calculate it
+    pub fn interval(&self, interval: Option<i64>) -> i64 {
+        let interval = interval.unwrap_or_else(|| {
+            let range_ms = (self.to - self.from) * 1000;
+            calculate_interval(range_ms, self.max_data_points, self.min_interval * 1000) / 1000
+        });
+        interval.max(self.min_interval).max(1)
+    }
+}
+
+/// `target` with the templating values filled in, ready for /api/ds/query
+pub fn build_query(
+    client: &Client,
+    panel: &Panel,
+    target: &Target,
+    templating: &HashMap<String, String>,
+    max_data_points: i64,
+    interval: i64,
+) -> Result<serde_json::Value, Failure> {
+    let mut query = target.query.clone();
+    visit_replace(&mut query, templating);
+    {
+        let query = query.as_object_mut().unwrap();
+        query.insert("maxDataPoints".to_string(), max_data_points.into());
+        query.insert("intervalMs".to_string(), (interval * 1000).into());
+        if let (Some(datasource), None) = (&panel.datasource, query.get("datasource")) {
+            let mut datasource = datasource.clone();
+            if let Some(name) = datasource.as_str() {
+                let ds = client.get(&format!(
+                    "/api/datasources/name/{}",
+                    crate::client::encode(name)
+                ))?;
+                datasource = serde_json::json!({"uid": ds["uid"]});
+            }
+            query.insert("datasource".to_string(), datasource);
+        }
+    }
+    Ok(query)
+}
+
+pub fn get_values(
+    client: &Client,
+    query: &serde_json::Value,
+    from: i64,
+    to: i64,
+) -> Result<serde_json::Value, Failure> {
+    let qarg = serde_json::Value::Object(serde_json::Map::from_iter([
+        (
+            "queries".to_string(),
+            serde_json::Value::Array(vec![query.clone()]),
+        ),
+        ("from".to_string(), (from * 1000).to_string().into()),
+        ("to".to_string(), (to * 1000).to_string().into()),
+    ]))
+    .to_string();
+    client.trace(1, || format!("query: {qarg}"));
+    client.post("/api/ds/query", &qarg)
+}
+
+pub fn parse_values(frames: &[Frame]) -> (Vec<i64>, Frames) {
+    let times = frames.first().map(Frame::times).unwrap_or_default();
+    // frames -> series -> _
+    let vals = frames.iter().map(Frame::series).collect();
+    (times, vals)
+}
+
+/// a display name for each series, like grafana's legend
+pub fn series_names(frames: &[Frame]) -> Vec<String> {
+    frames
+        .iter()
+        .flat_map(|frame| {
+            (1..frame.values.len()).map(move |k| {
+                let Some(field) = frame.fields.get(k) else {
+                    return "value".to_string();
+                };
+                if let Some(name) = &field.display_name {
+                    return name.clone();
+                }
+                let name = if field.name.is_empty() {
+                    "value"
+                } else {
+                    &field.name
+                };
+                if field.labels.is_empty() {
+                    return name.to_string();
+                }
+                let labels: Vec<_> = field
+                    .labels
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect();
+                format!("{name} {{{}}}", labels.join(", "))
+            })
+        })
+        .collect()
+}
+
+// the compared series are looked up by time, shifted forward by `compare`.
+// times are the first frame's, other frames may be shorter
+fn align(times: &[i64], ctimes: &[i64], cvals: Frames, compare: i64, interval: i64) -> Frames {
+    let near = |t: i64| {
+        let t = t - compare * 1000;
+        let k = ctimes.partition_point(|&ct| ct < t);
+        [k.checked_sub(1), Some(k)]
+            .into_iter()
+            .flatten()
+            .filter(|&k| k < ctimes.len())
+            .min_by_key(|&k| (ctimes[k] - t).abs())
+            .filter(|&k| (ctimes[k] - t).abs() <= interval * 1000 / 2)
+    };
+    let idxs: Vec<_> = times.iter().map(|&t| near(t)).collect();
+    cvals
+        .into_iter()
+        .map(|vvs| {
+            vvs.into_iter()
+                .map(|vs| {
+                    idxs.iter()
+                        .map(|k| k.and_then(|k| vs.get(k).copied().flatten()))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub struct Fetched {
+    // the name of the time field, "Time" usually
+    pub time_name: String,
+    pub times: Vec<i64>,
+    // any compared frames come after the `ncurrent` current series
+    pub vals: Frames,
+    pub ncurrent: usize,
+    pub names: Vec<String>,
+    // the color field config of each current series, if the datasource set one
+    pub colors: Vec<serde_json::Value>,
+    // the grafana live channel of each current frame from a streaming datasource
+    pub channels: Vec<Option<String>>,
+}
+
+pub struct Query<'c> {
+    pub client: &'c Client,
+    pub query: serde_json::Value,
+    pub refid: String,
+    pub interval: i64,
+    pub compare: Option<i64>,
+}
+
+// the frames of `refid`'s result, adding the datasource's notices to
+// `notices`, or why the query failed
+fn result_frames(
+    dsquery: &serde_json::Value,
+    refid: &str,
+    notices: &mut Vec<String>,
+) -> Result<Vec<Frame>, String> {
+    let mut res: QueryResponse =
+        decode(dsquery).map_err(|err| format!("unexpected response from /api/ds/query: {err}"))?;
+    let res = res.results.remove(refid).unwrap_or_default();
+    for notice in res.frames.iter().flat_map(|f| &f.notices) {
+        let notice = format!("{}: {}", notice.severity, notice.text);
+        if !notices.contains(&notice) {
+            notices.push(notice);
+        }
+    }
+    if let Some(err) = res.error {
+        let err = match res.status {
+            Some(status) => format!("{err} (status {status})"),
+            None => err,
+        };
+        // an error can come with what the datasource did manage to return
+        if res.frames.is_empty() {
+            return Err(err);
+        }
+        notices.push(format!("warning: partial result, {err}"));
+    }
+    Ok(res.frames)
+}
+
+// whether `frames` has no rows to draw
+fn is_empty(frames: &[Frame]) -> bool {
+    frames.first().is_none_or(|f| f.times().is_empty())
+}
+
+/// what a query returned
+pub struct Fetch {
+    // Ok(None) when the query ran but had nothing in the range
+    pub data: Result<Option<Fetched>, String>,
+    // warnings and infos from the datasource, like "warning: ..."
+    pub notices: Vec<String>,
+}
+
+impl Query<'_> {
+    /// runs the query for `from`..`to`, failing if grafana couldn't be asked
+    /// at all. a query that grafana ran but failed is in the fetch's data
+    pub fn fetch(&self, from: i64, to: i64) -> Result<Fetch, Failure> {
+        let dsquery = get_values(self.client, &self.query, from, to)?;
+        let mut notices = vec![];
+        let data = self.fetched(&dsquery, from, to, &mut notices);
+        Ok(Fetch { data, notices })
+    }
+
+    fn fetched(
+        &self,
+        dsquery: &serde_json::Value,
+        from: i64,
+        to: i64,
+        notices: &mut Vec<String>,
+    ) -> Result<Option<Fetched>, String> {
+        let frames = result_frames(dsquery, &self.refid, notices)?;
+        if is_empty(&frames) {
+            return Ok(None);
+        }
+        let channels = frames.iter().map(|f| f.channel.clone()).collect();
+        let time_name = (frames[0].fields.first())
+            .map(|f| f.name.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "time".to_string());
+        let names = series_names(&frames);
+        let colors = frames
+            .iter()
+            .flat_map(|frame| {
+                (1..frame.values.len()).map(|k| {
+                    let field = frame.fields.get(k);
+                    field.map_or(serde_json::Value::Null, |f| f.color.clone())
+                })
+            })
+            .collect();
+        let (times, mut vals) = parse_values(&frames);
+        let ncurrent = vals.iter().map(Vec::len).sum::<usize>();
+        if let Some(compare) = self.compare {
+            let dsquery = get_values(self.client, &self.query, from - compare, to - compare);
+            let cframes = dsquery
+                .map_err(|failure| failure.to_string())
+                .and_then(|dsquery| result_frames(&dsquery, &self.refid, notices));
+            match cframes {
+                Ok(cframes) if !is_empty(&cframes) => {
+                    let (ctimes, cvals) = parse_values(&cframes);
+                    vals.extend(align(&times, &ctimes, cvals, compare, self.interval));
+                }
+                Ok(_) => {}
+                Err(err) => notices.push(format!("warning: compared query failed, {err}")),
+            }
+        }
+        Ok(Some(Fetched {
+            time_name,
+            times,
+            vals,
+            ncurrent,
+            names,
+            colors,
+            channels,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_uneven_frames() {
+        let times = [60_000, 120_000, 180_000];
+        // the second compared frame has fewer rows than the first
+        let ctimes = [0, 60_000, 120_000];
+        let cvals = vec![
+            vec![vec![Some(1.0), Some(2.0), Some(3.0)]],
+            vec![vec![Some(4.0)], vec![]],
+        ];
+        let aligned = align(&times, &ctimes, cvals, 60, 60);
+        assert_eq!(
+            aligned,
+            vec![
+                vec![vec![Some(1.0), Some(2.0), Some(3.0)]],
+                vec![vec![Some(4.0), None, None], vec![None, None, None]],
+            ]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+// a plot drawn in pixels, for terminals that show images. lines are
+// antialiased, text is a 5x7 pixel font scaled up to the terminal's cells.
+
+use crate::plot::{Layout, Plot};
+use crate::tz::Tz;
+
+/// rgb pixels, row after row
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+/// the colors to draw with, as 0xrrggbb
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub fg: u32,
+    pub bg: u32,
+}
+
+impl Theme {
+    /// grafana's dark theme, for terminals that don't tell their colors
+    pub const DARK: Theme = Theme {
+        fg: 0xccccdc,
+        bg: 0x181b1f,
+    };
+}
+
+// `t` of `a` over `b`
+fn mix(a: u32, b: u32, t: f64) -> u32 {
+    [16, 8, 0].into_iter().fold(0, |rgb, shift| {
+        let (a, b) = (f64::from(a >> shift & 0xff), f64::from(b >> shift & 0xff));
+        rgb | ((a * t + b * (1.0 - t)).round() as u32) << shift
+    })
+}
+
+// the columns of each glyph from ' ' to '~', the top row in the lowest bit
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5f, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50],
+    [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1c, 0x00],
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08],
+    [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e],
+    [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3c, 0x4a, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1e],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x00, 0x08, 0x14, 0x22, 0x41],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x41, 0x22, 0x14, 0x08, 0x00],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e],
+    [0x7e, 0x11, 0x11, 0x11, 0x7e],
+    [0x7f, 0x49, 0x49, 0x49, 0x36],
+    [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c],
+    [0x7f, 0x49, 0x49, 0x49, 0x41],
+    [0x7f, 0x09, 0x09, 0x01, 0x01],
+    [0x3e, 0x41, 0x41, 0x51, 0x32],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f],
+    [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01],
+    [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40],
+    [0x7f, 0x02, 0x04, 0x02, 0x7f],
+    [0x7f, 0x04, 0x08, 0x10, 0x7f],
+    [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06],
+    [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    [0x7f, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01],
+    [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f],
+    [0x7f, 0x20, 0x18, 0x20, 0x7f],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x00, 0x7f, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x41, 0x41, 0x7f, 0x00, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00],
+    [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38],
+    [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f],
+    [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7e, 0x09, 0x01, 0x02],
+    [0x08, 0x14, 0x54, 0x54, 0x3c],
+    [0x7f, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7d, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3d, 0x00],
+    [0x00, 0x7f, 0x10, 0x28, 0x44],
+    [0x00, 0x41, 0x7f, 0x40, 0x00],
+    [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78],
+    [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7c],
+    [0x7c, 0x08, 0x04, 0x04, 0x08],
+    [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20],
+    [0x3c, 0x40, 0x40, 0x20, 0x7c],
+    [0x1c, 0x20, 0x40, 0x20, 0x1c],
+    [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x0c, 0x50, 0x50, 0x50, 0x3c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x10, 0x08, 0x08, 0x10, 0x08],
+];
+
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        ' '..='~' => FONT[c as usize - 0x20],
+        // for units
+        '°' => [0x00, 0x06, 0x09, 0x09, 0x06],
+        'µ' => [0x7e, 0x20, 0x20, 0x10, 0x3e],
+        _ => [0x7f, 0x41, 0x41, 0x41, 0x7f],
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, bg: u32) -> Image {
+        let pixel = [(bg >> 16) as u8, (bg >> 8) as u8, bg as u8];
+        Image {
+            width,
+            height,
+            rgb: pixel.repeat(width as usize * height as usize),
+        }
+    }
+
+    // `rgb` over the pixel at `x`, `y` with `alpha`
+    fn blend(&mut self, x: i64, y: i64, rgb: u32, alpha: f64) {
+        if x < 0 || y < 0 || x >= i64::from(self.width) || y >= i64::from(self.height) {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        let pixel = &mut self.rgb[i..i + 3];
+        let under = u32::from(pixel[0]) << 16 | u32::from(pixel[1]) << 8 | u32::from(pixel[2]);
+        let rgb = mix(rgb, under, alpha);
+        pixel.copy_from_slice(&[(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]);
+    }
+
+    // the pixels from `x0`, `y0` up to `x1`, `y1`
+    fn fill(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, rgb: u32) {
+        let (x0, y0) = (x0.round() as i64, y0.round() as i64);
+        let (x1, y1) = (x1.round() as i64, y1.round() as i64);
+        for y in y0..y1.max(y0 + 1) {
+            for x in x0..x1.max(x0 + 1) {
+                self.blend(x, y, rgb, 1.0);
+            }
+        }
+    }
+
+    // `text` with its baseline at `y`, each font pixel `scale` pixels
+    fn text(&mut self, x: f64, y: f64, text: &str, scale: u32, rgb: u32, anchor: Anchor) {
+        let s = i64::from(scale);
+        let width = (text.chars().count() as i64 * 6 - 1) * s;
+        let x = x.round() as i64
+            - match anchor {
+                Anchor::Start => 0,
+                Anchor::Middle => width / 2,
+                Anchor::End => width,
+            };
+        let top = y.round() as i64 - 7 * s;
+        for (k, c) in text.chars().enumerate() {
+            let left = x + k as i64 * 6 * s;
+            for (col, bits) in glyph(c).into_iter().enumerate() {
+                for row in (0..7).filter(|row| bits >> row & 1 == 1) {
+                    for (dx, dy) in (0..s).flat_map(|dx| (0..s).map(move |dy| (dx, dy))) {
+                        self.blend(left + col as i64 * s + dx, top + row * s + dy, rgb, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    // lines through each run of points, `width` wide, dashed `on` and `off`
+    // pixels along the line if `dash`
+    fn stroke(
+        &mut self,
+        runs: &[Vec<(f64, f64)>],
+        width: f64,
+        rgb: u32,
+        alpha: f64,
+        dash: Option<(f64, f64)>,
+    ) {
+        let (w, h) = (i64::from(self.width), i64::from(self.height));
+        // how much of each pixel the line covers, where segments overlap at
+        // their joins the most of them, so the joins don't get darker
+        let mut cover = vec![0f64; (w * h) as usize];
+        let r = width / 2.0;
+        for run in runs {
+            let mut along = 0.0;
+            // a value between missing ones is a dot
+            let segments: Vec<_> = match run[..] {
+                [p] => vec![(p, p)],
+                _ => run.windows(2).map(|ps| (ps[0], ps[1])).collect(),
+            };
+            for ((ax, ay), (bx, by)) in segments {
+                let (dx, dy) = (bx - ax, by - ay);
+                let len2 = dx * dx + dy * dy;
+                let x0 = ((ax.min(bx) - r - 1.0).floor() as i64).max(0);
+                let x1 = ((ax.max(bx) + r + 1.0).ceil() as i64).min(w - 1);
+                let y0 = ((ay.min(by) - r - 1.0).floor() as i64).max(0);
+                let y1 = ((ay.max(by) + r + 1.0).ceil() as i64).min(h - 1);
+                for y in y0..=y1 {
+                    for x in x0..=x1 {
+                        let (cx, cy) = (x as f64 + 0.5, y as f64 + 0.5);
+                        // the nearest point of the segment
+                        let t = if len2 == 0.0 {
+                            0.0
+                        } else {
+                            (((cx - ax) * dx + (cy - ay) * dy) / len2).clamp(0.0, 1.0)
+                        };
+                        let d = (cx - ax - t * dx).hypot(cy - ay - t * dy);
+                        let on = dash
+                            .is_none_or(|(on, off)| (along + t * len2.sqrt()) % (on + off) < on);
+                        if on {
+                            let c = &mut cover[(y * w + x) as usize];
+                            *c = c.max((r + 0.5 - d).clamp(0.0, 1.0));
+                        }
+                    }
+                }
+                along += len2.sqrt();
+            }
+        }
+        for (i, c) in cover.into_iter().enumerate() {
+            if c > 0.0 {
+                self.blend(i as i64 % w, i as i64 / w, rgb, c * alpha);
+            }
+        }
+    }
+}
+
+/// draws `plot` as a `width` x `height` image in `theme`'s colors, the text
+/// `scale` times the size of its 5x7 pixel font, times in `tz`
+pub fn draw(plot: &Plot, width: u32, height: u32, scale: u32, theme: Theme, tz: &Tz) -> Image {
+    // glyphs are 6 pixels apart, as wide as the layout takes text to be
+    let s = f64::from(scale);
+    let font = 10.0 * s;
+    let l = Layout::new(plot, width, height, font, tz);
+    let mut image = Image::new(width, height, theme.bg);
+    let text = theme.fg;
+    let faint = mix(theme.fg, theme.bg, 0.6);
+    let grid = mix(theme.fg, theme.bg, 0.12);
+    let axis = mix(theme.fg, theme.bg, 0.3);
+
+    let baseline = font * 2.0;
+    image.text(font, baseline, &plot.title, scale, text, Anchor::Start);
+    image.text(l.right, baseline, &l.range, scale, faint, Anchor::End);
+
+    // the grid behind the values, with their labels
+    for (y, label) in &l.yticks {
+        image.fill(l.left, *y, l.right, y + 1.0, grid);
+        let y = y + font * 0.35;
+        image.text(l.left - font * 0.5, y, label, scale, text, Anchor::End);
+    }
+    for (x, label) in &l.xticks {
+        image.fill(*x, l.top, x + 1.0, l.bottom, grid);
+        let y = l.bottom + font * 1.5;
+        image.text(*x, y, label, scale, text, Anchor::Middle);
+    }
+    image.fill(l.left, l.top, l.left + 1.0, l.bottom, axis);
+    image.fill(l.left, l.bottom, l.right, l.bottom + 1.0, axis);
+
+    // compared series first, under the current ones
+    let lines = l.lines(plot);
+    let order = (plot.ncurrent..lines.len()).chain(0..plot.ncurrent.min(lines.len()));
+    for k in order {
+        let (rgb, compared) = plot.color(k);
+        let (alpha, dash) = if compared {
+            (0.6, Some((4.0 * s, 3.0 * s)))
+        } else {
+            (1.0, None)
+        };
+        image.stroke(&lines[k], 1.5 * s, rgb, alpha, dash);
+    }
+
+    for (k, &(x, y)) in l.legend.iter().enumerate() {
+        let (rgb, _) = plot.color(k);
+        let top = y - font * 0.4 - s;
+        image.fill(x, top, x + font * 1.5, top + 2.0 * s, rgb);
+        image.text(
+            x + font * 2.0,
+            y,
+            &plot.names[k],
+            scale,
+            text,
+            Anchor::Start,
+        );
+    }
+    image
+}
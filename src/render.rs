@@ -0,0 +1,190 @@
+use std::io::Write;
+
+use crate::time::timestamp;
+use crate::tz::Tz;
+
+/// frames -> series -> values, as parsed from /api/ds/query
+pub type Frames = Vec<Vec<Vec<Option<f64>>>>;
+
+const GRID: &str = "90";
+
+/// a text chart, one row per time, with values running across the columns
+pub struct Chart {
+    pub times: Vec<i64>,
+    scaled: Vec<Vec<Vec<Option<u16>>>>,
+    // series after the first `ncurrent` are compared series
+    pub ncurrent: usize,
+    pub min: f64,
+    pub max: f64,
+    cols: u16,
+    // hidden series, a compared series is hidden with its current series
+    hidden: Vec<bool>,
+    // SGR parameters of the current series, none to write no colors at all
+    colors: Vec<String>,
+}
+
+impl Chart {
+    /// `cols` is the width of the plot, not counting the time stamps
+    pub fn new(
+        times: Vec<i64>,
+        vals: Frames,
+        ncurrent: usize,
+        cols: u16,
+        hidden: Vec<bool>,
+        colors: Vec<String>,
+    ) -> Chart {
+        let mut chart = Chart {
+            times: vec![],
+            scaled: vec![],
+            ncurrent,
+            min: f64::INFINITY,
+            max: -f64::INFINITY,
+            cols,
+            hidden,
+            colors,
+        };
+        for (k, v) in vals.iter().flatten().enumerate() {
+            if chart.is_hidden(k) {
+                continue;
+            }
+            for &v in v.iter().flatten() {
+                chart.min = chart.min.min(v);
+                chart.max = chart.max.max(v);
+            }
+        }
+        chart.update(times, vals, ncurrent);
+        chart
+    }
+
+    // compared series are drawn faint in the color of their current series
+    fn color(&self, k: usize) -> String {
+        match k.checked_sub(self.ncurrent) {
+            Some(k) => format!("2;{}", self.colors.get(k).map_or("", String::as_str)),
+            None => self.colors.get(k).cloned().unwrap_or_default(),
+        }
+    }
+
+    fn is_hidden(&self, k: usize) -> bool {
+        let k = k.checked_sub(self.ncurrent).unwrap_or(k);
+        self.hidden.get(k).copied().unwrap_or(false)
+    }
+
+    /// replaces the chart's data, keeping its scale
+    pub fn update(&mut self, times: Vec<i64>, vals: Frames, ncurrent: usize) {
+        let (min, cols) = (self.min, self.cols);
+        let scale = f64::from(cols - 1) / (self.max - self.min);
+        let scale = |v: f64| {
+            let v = (v - min) * scale;
+            if v.is_finite() && v >= 0.0 && v < f64::from(cols) {
+                // SAFETY: finite (and not NaN), and fits in u16 asserted above
+                Some(unsafe { v.to_int_unchecked::<u16>() })
+            } else {
+                None
+            }
+        };
+        self.scaled = vals
+            .into_iter()
+            .map(|vvs| {
+                vvs.into_iter()
+                    .map(|vs| vs.into_iter().map(|v| v.and_then(scale)).collect())
+                    .collect()
+            })
+            .collect();
+        self.times = times;
+        self.ncurrent = ncurrent;
+    }
+
+    /// writes the row joining the values at `i - 1` and `i`, with the value
+    /// scale overlaid if `header`, and prefixed with the time if `label`
+    pub fn write_row(
+        &self,
+        w: &mut impl Write,
+        i: usize,
+        header: bool,
+        label: bool,
+        tz: &Tz,
+    ) -> std::io::Result<()> {
+        let (min, max, cols) = (self.min, self.max, self.cols);
+        let mut hdr = vec![];
+        if header {
+            let step = (max - min) / f64::from(cols) * 16.0;
+            for j in 0..=((cols + 1) / 16) {
+                write!(hdr, " {:<15.2}", min + step * f64::from(j))?;
+            }
+        }
+        if label {
+            let mut buf = [b' '; 9];
+            let time_s = timestamp(self.times[i], tz, &mut buf);
+            write!(w, "{time_s} ")?;
+        } else {
+            write!(w, "         ")?;
+        }
+        let mut hdr = hdr.into_iter();
+        for j in 0..cols {
+            let s = self.scaled.iter().flat_map(|v| v.iter()).enumerate();
+            let acc = hdr
+                .next()
+                .filter(|b| *b != b' ')
+                .map(|b| (GRID.to_string(), char::from(b)));
+            let fold = |k: usize, vs: &[_]| {
+                if self.is_hidden(k) {
+                    return None;
+                }
+                // frames other than the first may have fewer rows
+                let (x, xp) = match (vs.get(i), vs.get(i - 1)) {
+                    (Some(&Some(x)), Some(&Some(xp))) => (x, xp),
+                    _ => return None,
+                };
+                let color = self.color(k);
+                if (x < j && j < xp) || (xp < j && j < x) {
+                    Some((color, '-'))
+                } else if x == j && xp == j {
+                    Some((color, '|'))
+                } else if x == j {
+                    Some((color, '.'))
+                } else if xp == j {
+                    Some((color, '\''))
+                } else {
+                    None
+                }
+            };
+            let s = s
+                .fold(acc, |acc, (k, vs)| acc.or_else(|| fold(k, vs)))
+                .unwrap_or_else(|| {
+                    if j % 16 == 0 {
+                        (GRID.to_string(), '|')
+                    } else {
+                        (String::new(), ' ')
+                    }
+                });
+            if s.0.is_empty() || self.colors.is_empty() {
+                write!(w, "{}", s.1)?;
+            } else {
+                write!(w, "\x1b[{}m{}\x1b[0m", s.0, s.1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_row_uneven_frames() {
+        let vals = vec![
+            vec![vec![Some(0.0), Some(1.0), Some(2.0)]],
+            vec![vec![Some(2.0)]],
+        ];
+        let chart = Chart::new(vec![0, 1000, 2000], vals, 2, 3, vec![], vec![]);
+        let row = |i| {
+            let mut out = vec![];
+            (chart.write_row(&mut out, i, false, false, &Tz::utc())).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        // the second frame ends after its first row, and draws nothing past it
+        assert_eq!(row(1), "         '. ");
+        assert_eq!(row(2), "         |'.");
+    }
+}
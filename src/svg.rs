@@ -0,0 +1,181 @@
+// a plot as a standalone svg, dark on white like a printed page, for pasting
+// into documents. the text is left to the viewer's sans-serif font.
+
+use std::io::Write;
+
+use crate::plot::{Layout, Plot};
+use crate::tz::Tz;
+
+const TEXT: &str = "#464c54";
+const FAINT: &str = "#8e8e8e";
+const GRID: &str = "#e6e7e9";
+const AXIS: &str = "#b8bcc2";
+
+// text for between tags or in an attribute
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// writes `plot` as a `width` x `height` svg, times in `tz`
+pub fn write(
+    w: &mut impl Write,
+    plot: &Plot,
+    width: u32,
+    height: u32,
+    tz: &Tz,
+) -> std::io::Result<()> {
+    let font = 12.0;
+    let l = Layout::new(plot, width, height, font, tz);
+    writeln!(
+        w,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="{font}" fill="{TEXT}">"#
+    )?;
+    writeln!(w, r##"<rect width="100%" height="100%" fill="#ffffff"/>"##)?;
+    let baseline = font * 2.0;
+    writeln!(
+        w,
+        r#"<text x="{:.1}" y="{baseline:.1}" font-weight="bold">{}</text>"#,
+        font,
+        escape(&plot.title)
+    )?;
+    writeln!(
+        w,
+        r#"<text x="{:.1}" y="{baseline:.1}" text-anchor="end" fill="{FAINT}">{}</text>"#,
+        l.right,
+        escape(&l.range)
+    )?;
+
+    // the grid behind the values, with their labels
+    for (y, label) in &l.yticks {
+        writeln!(
+            w,
+            r#"<path d="M{:.1} {y:.1}H{:.1}" stroke="{GRID}"/><text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"#,
+            l.left,
+            l.right,
+            l.left - font * 0.5,
+            y + font * 0.35,
+            escape(label)
+        )?;
+    }
+    for (x, label) in &l.xticks {
+        writeln!(
+            w,
+            r#"<path d="M{x:.1} {:.1}V{:.1}" stroke="{GRID}"/><text x="{x:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+            l.top,
+            l.bottom,
+            l.bottom + font * 1.5,
+            escape(label)
+        )?;
+    }
+    writeln!(
+        w,
+        r#"<path d="M{:.1} {:.1}V{:.1}H{:.1}" fill="none" stroke="{AXIS}"/>"#,
+        l.left, l.top, l.bottom, l.right
+    )?;
+
+    // compared series first, under the current ones
+    let lines = l.lines(plot);
+    let order = (plot.ncurrent..lines.len()).chain(0..plot.ncurrent.min(lines.len()));
+    for k in order {
+        let (rgb, compared) = plot.color(k);
+        let style = if compared {
+            r#" stroke-dasharray="4 3" stroke-opacity="0.6""#
+        } else {
+            ""
+        };
+        write!(
+            w,
+            r##"<g fill="none" stroke="#{rgb:06x}" stroke-width="1.5" stroke-linejoin="round"{style}>"##
+        )?;
+        for run in &lines[k] {
+            match run[..] {
+                // a value between missing ones is a dot
+                [(x, y)] => write!(
+                    w,
+                    r##"<circle cx="{x:.1}" cy="{y:.1}" r="1.5" fill="#{rgb:06x}"/>"##
+                )?,
+                _ => {
+                    let points: Vec<_> =
+                        run.iter().map(|(x, y)| format!("{x:.1} {y:.1}")).collect();
+                    write!(w, r#"<path d="M{}"/>"#, points.join("L"))?;
+                }
+            }
+        }
+        writeln!(w, "</g>")?;
+    }
+
+    for (k, &(x, y)) in l.legend.iter().enumerate() {
+        let (rgb, _) = plot.color(k);
+        writeln!(
+            w,
+            r##"<rect x="{x:.1}" y="{:.1}" width="{:.1}" height="3" fill="#{rgb:06x}"/><text x="{:.1}" y="{y:.1}">{}</text>"##,
+            y - font * 0.4 - 1.5,
+            font * 1.5,
+            x + font * 2.0,
+            escape(&plot.names[k])
+        )?;
+    }
+    writeln!(w, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tags nest and close, and every & starts an entity
+    fn well_formed(svg: &str) {
+        let mut open = vec![];
+        let mut rest = svg;
+        while let Some(start) = rest.find('<') {
+            let text = &rest[..start];
+            assert!(!text.contains('>'), "stray > in {text:?}");
+            let end = start + rest[start..].find('>').expect("unclosed tag");
+            let tag = &rest[start + 1..end];
+            assert!(!tag.contains('<'), "< in tag {tag:?}");
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "closing {name}");
+            } else if !tag.ends_with('/') {
+                open.push(tag.split(' ').next().unwrap());
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(open.is_empty(), "left open: {open:?}");
+        for (i, _) in svg.match_indices('&') {
+            let entity = &svg[i..svg[i..].find(';').map_or(svg.len(), |e| i + e + 1)];
+            assert!(
+                ["&amp;", "&lt;", "&gt;", "&quot;"].contains(&entity),
+                "{entity:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn escaped() {
+        let plot = Plot {
+            title: "<b>errors & \"retries\"</b>".to_string(),
+            times: vec![0, 60_000, 120_000, 180_000],
+            series: vec![
+                vec![Some(1.0), None, Some(3.0), Some(2.0)],
+                vec![Some(0.5), Some(1.5), Some(1.0), None],
+            ],
+            ncurrent: 1,
+            names: vec!["a<b & c>d".to_string()],
+            colors: vec![0xff0000],
+            unit: String::new(),
+            decimals: None,
+        };
+        let mut out = vec![];
+        write(&mut out, &plot, 900, 360, &Tz::utc()).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        well_formed(&svg);
+        assert!(svg.contains(">&lt;b&gt;errors &amp; &quot;retries&quot;&lt;/b&gt;</text>"));
+        assert!(svg.contains(">a&lt;b &amp; c&gt;d</text>"));
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+        // the dot between missing values, and the compared series dashed
+        assert!(svg.contains("<circle "));
+        assert!(svg.contains("stroke-dasharray"));
+    }
+}
@@ -0,0 +1,204 @@
+// the rows of a query as json, csv or tsv for other tools, a column for the
+// time and one for each series. json is an object per line, so rows can be
+// written as they come when following.
+
+use std::io::Write;
+
+use crate::render::Frames;
+use crate::time::rfc3339;
+use crate::tz::Tz;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl Format {
+    pub fn named(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "tsv" => Some(Format::Tsv),
+            _ => None,
+        }
+    }
+}
+
+pub struct Table {
+    format: Format,
+    // the time's column, then the series'
+    columns: Vec<String>,
+    // the time of the last row written, only later rows are new
+    written: Option<i64>,
+}
+
+impl Table {
+    pub fn new(format: Format, time_name: &str, names: &[String]) -> Table {
+        Table {
+            format,
+            columns: std::iter::once(time_name)
+                .chain(names.iter().map(String::as_str))
+                .map(str::to_string)
+                .collect(),
+            written: None,
+        }
+    }
+
+    /// the column names, json has them in every row instead
+    pub fn write_header(&self, w: &mut impl Write) -> std::io::Result<()> {
+        match self.format {
+            Format::Json => Ok(()),
+            _ => self.write_line(w, self.columns.iter().map(String::as_str)),
+        }
+    }
+
+    /// writes the rows of `times` after the ones already written, with the
+    /// values of the first series of `vals` that there are columns for
+    pub fn write_rows(
+        &mut self,
+        w: &mut impl Write,
+        times: &[i64],
+        vals: &Frames,
+        tz: &Tz,
+    ) -> std::io::Result<()> {
+        let series: Vec<_> = vals.iter().flatten().take(self.columns.len() - 1).collect();
+        for (i, &time) in times.iter().enumerate() {
+            if self.written.is_some_and(|t| time <= t) {
+                continue;
+            }
+            let values = series.iter().map(|vs| vs.get(i).copied().flatten());
+            match self.format {
+                Format::Json => {
+                    let fields: Vec<_> = std::iter::once(rfc3339(time / 1000, tz).into())
+                        .chain(values.map(serde_json::Value::from))
+                        .zip(&self.columns)
+                        .map(|(v, name)| format!("{}:{v}", serde_json::Value::from(name.as_str())))
+                        .collect();
+                    writeln!(w, "{{{}}}", fields.join(","))?;
+                }
+                _ => {
+                    let values: Vec<_> = values
+                        .map(|v| v.map(|v| v.to_string()).unwrap_or_default())
+                        .collect();
+                    let time = rfc3339(time / 1000, tz);
+                    self.write_line(
+                        w,
+                        std::iter::once(time.as_str()).chain(values.iter().map(String::as_str)),
+                    )?;
+                }
+            }
+            self.written = Some(time);
+        }
+        w.flush()
+    }
+
+    fn write_line<'f>(
+        &self,
+        w: &mut impl Write,
+        fields: impl Iterator<Item = &'f str>,
+    ) -> std::io::Result<()> {
+        let fields: Vec<_> = match self.format {
+            // quoted only when they have to be
+            Format::Csv => fields
+                .map(|f| {
+                    if f.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", f.replace('"', "\"\""))
+                    } else {
+                        f.to_string()
+                    }
+                })
+                .collect(),
+            // tsv has no quoting, tabs and newlines can't be in a field
+            _ => fields.map(|f| f.replace(['\t', '\n', '\r'], " ")).collect(),
+        };
+        let sep = if self.format == Format::Csv {
+            ","
+        } else {
+            "\t"
+        };
+        writeln!(w, "{}", fields.join(sep))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(table: &mut Table, times: &[i64], vals: &Frames) -> String {
+        let mut out = vec![];
+        table.write_rows(&mut out, times, vals, &Tz::utc()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let table = Table::new(
+            Format::Csv,
+            "Time",
+            &names(&["a,b", "say \"hi\"", "line\nbreak", "plain\tname"]),
+        );
+        let mut out = vec![];
+        table.write_header(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Time,\"a,b\",\"say \"\"hi\"\"\",\"line\nbreak\",plain\tname\n"
+        );
+    }
+
+    #[test]
+    fn tsv_quoting() {
+        let table = Table::new(Format::Tsv, "Time", &names(&["a\tb", "c,\"d\"", "e\nf"]));
+        let mut out = vec![];
+        table.write_header(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Time\ta b\tc,\"d\"\te f\n");
+    }
+
+    #[test]
+    fn json_rows() {
+        let mut table = Table::new(Format::Json, "Time", &names(&["up", "say \"hi\""]));
+        let mut out = vec![];
+        table.write_header(&mut out).unwrap();
+        assert!(out.is_empty());
+        let vals = vec![
+            vec![vec![Some(1.5), None]],
+            vec![vec![Some(2.0), Some(-3.0)]],
+        ];
+        let rows = write(&mut table, &[0, 60_000], &vals);
+        assert_eq!(
+            rows,
+            "{\"Time\":\"1970-01-01T00:00:00Z\",\"up\":1.5,\"say \\\"hi\\\"\":2.0}\n\
+             {\"Time\":\"1970-01-01T00:01:00Z\",\"up\":null,\"say \\\"hi\\\"\":-3.0}\n"
+        );
+        for row in rows.lines() {
+            let row: serde_json::Value = serde_json::from_str(row).unwrap();
+            assert_eq!(row.as_object().unwrap().len(), 3);
+        }
+    }
+
+    #[test]
+    fn only_new_rows() {
+        // series past the columns, like compared ones, are left out
+        let mut table = Table::new(Format::Csv, "Time", &names(&["a"]));
+        let vals = vec![
+            vec![vec![Some(1.0), Some(2.0)]],
+            vec![vec![Some(9.0), Some(9.0)]],
+        ];
+        assert_eq!(
+            write(&mut table, &[0, 60_000], &vals),
+            "1970-01-01T00:00:00Z,1\n1970-01-01T00:01:00Z,2\n"
+        );
+        // following fetches an interval before the last row again
+        let vals = vec![vec![vec![Some(2.5), Some(3.0), None]]];
+        assert_eq!(
+            write(&mut table, &[60_000, 120_000, 180_000], &vals),
+            "1970-01-01T00:02:00Z,3\n1970-01-01T00:03:00Z,\n"
+        );
+        assert_eq!(write(&mut table, &[120_000, 180_000], &vals), "");
+    }
+}
@@ -0,0 +1,261 @@
+// raw terminal input for the pickers and --tui. stdin goes in raw mode so
+// single keys can be read, and whatever was set up is undone when the guard
+// drops or the process exits. the terminal is also asked what it can draw.
+
+use std::io::{Read as _, Write as _};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static SAVED: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+
+// what restore has to undo
+const NONE: u8 = 0;
+const INLINE: u8 = 1;
+const FULLSCREEN: u8 = 2;
+static MODE: AtomicU8 = AtomicU8::new(NONE);
+
+extern "C" fn restore() {
+    if let Some(saved) = SAVED.get() {
+        // SAFETY: ffi, saved was filled in by tcgetattr
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, saved) };
+    }
+    match MODE.swap(NONE, Ordering::Relaxed) {
+        INLINE => {
+            let mut stderr = std::io::stderr();
+            let _ = stderr.write_all(b"\x1b[?7h\x1b[?25h");
+            let _ = stderr.flush();
+        }
+        FULLSCREEN => {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x1b[?7h\x1b[?25h\x1b[?1049l");
+            let _ = stdout.flush();
+        }
+        _ => {}
+    }
+}
+
+/// whether stdin and `fd` are both terminals
+pub fn isatty(fd: i32) -> bool {
+    // SAFETY: just ffi
+    unsafe { libc::isatty(0) == 1 && libc::isatty(fd) == 1 }
+}
+
+// restores the terminal when dropped, or on exit if an error ends the process
+pub struct Term;
+
+impl Term {
+    /// raw mode for drawing below the cursor on stderr
+    pub fn inline() -> Result<Term, String> {
+        let term = Term::raw()?;
+        MODE.store(INLINE, Ordering::Relaxed);
+        // long lines are cut off rather than wrapped, so line counts hold
+        eprint!("\x1b[?7l");
+        Ok(term)
+    }
+
+    /// raw mode on the alternate screen, for drawing over all of stdout
+    pub fn fullscreen() -> Result<Term, String> {
+        let term = Term::raw()?;
+        MODE.store(FULLSCREEN, Ordering::Relaxed);
+        // long lines are cut off rather than wrapped, so the screen never scrolls
+        print!("\x1b[?1049h\x1b[?25l\x1b[?7l");
+        let _ = std::io::stdout().flush();
+        Ok(term)
+    }
+
+    fn raw() -> Result<Term, String> {
+        // SAFETY: ffi, termios is plain old data filled in by tcgetattr
+        let mut tio = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::isatty(0) } != 1 || unsafe { libc::tcgetattr(0, &mut tio) } != 0 {
+            return Err("stdin is not a terminal".to_string());
+        }
+        if SAVED.set(tio).is_ok() {
+            // SAFETY: ffi, restore is safe to call at any point
+            unsafe { libc::atexit(restore) };
+        }
+        // like cfmakeraw, but output processing stays on so '\n' still works
+        tio.c_iflag &= !(libc::IGNBRK | libc::BRKINT | libc::ICRNL | libc::IXON);
+        tio.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        // reads return after 100ms without input, so follow mode can refresh
+        tio.c_cc[libc::VMIN] = 0;
+        tio.c_cc[libc::VTIME] = 1;
+        // SAFETY: ffi
+        if unsafe { libc::tcsetattr(0, libc::TCSANOW, &tio) } != 0 {
+            return Err("unable to put the terminal in raw mode".to_string());
+        }
+        Ok(Term)
+    }
+}
+
+impl Drop for Term {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+#[derive(PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Tab,
+    Enter,
+    Backspace,
+    Esc,
+    // isig is off, so ctrl-c comes in as Ctrl('c')
+    Ctrl(char),
+    Char(char),
+}
+
+// bytes read past the last key, typed ahead or pasted
+static PENDING: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+
+/// the next key, or None if there was none within 100ms
+pub fn read_key() -> Option<Key> {
+    let mut pending = PENDING.lock().unwrap();
+    if pending.is_empty() {
+        let mut buf = [0; 64];
+        let n = std::io::stdin().read(&mut buf).ok()?;
+        pending.extend_from_slice(&buf[..n]);
+    }
+    let (key, len) = match pending[..] {
+        [] => return None,
+        [0x1b] => (Some(Key::Esc), 1),
+        [0x1b, b'[' | b'O', b'A', ..] => (Some(Key::Up), 3),
+        [0x1b, b'[' | b'O', b'B', ..] => (Some(Key::Down), 3),
+        [0x1b, b'[' | b'O', b'C', ..] => (Some(Key::Right), 3),
+        [0x1b, b'[' | b'O', b'D', ..] => (Some(Key::Left), 3),
+        // skip other escape sequences up to their final byte
+        [0x1b, b'[', ..] => {
+            let end = pending[2..].iter().position(|b| (0x40..=0x7e).contains(b));
+            (None, end.map_or(pending.len(), |end| end + 3))
+        }
+        [0x1b, ..] => (None, 2),
+        [b'\t', ..] => (Some(Key::Tab), 1),
+        [b'\r' | b'\n', ..] => (Some(Key::Enter), 1),
+        [0x7f | 0x08, ..] => (Some(Key::Backspace), 1),
+        [c @ 0x01..=0x1a, ..] => (Some(Key::Ctrl(char::from(c - 1 + b'a'))), 1),
+        _ => {
+            let s = match std::str::from_utf8(&pending) {
+                Ok(s) => s,
+                Err(err) => std::str::from_utf8(&pending[..err.valid_up_to()]).unwrap(),
+            };
+            match s.chars().next() {
+                Some(c) => (Some(Key::Char(c)), c.len_utf8()),
+                None => (None, 1),
+            }
+        }
+    };
+    let len = len.min(pending.len());
+    pending.drain(..len);
+    key
+}
+
+pub fn winsize(fd: i32) -> (u16, u16) {
+    let mut winsz = libc::winsize {
+        ws_col: 80,
+        ws_row: 24,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: just ffi
+    unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsz as *mut _) };
+    (winsz.ws_row.max(8), winsz.ws_col.max(20))
+}
+
+/// what the terminal told about drawing images
+#[derive(Default)]
+pub struct Caps {
+    pub sixel: bool,
+    pub kitty: bool,
+    // the size of a character cell in pixels
+    pub cell: Option<(u16, u16)>,
+    // the text and background colors, 0xrrggbb
+    pub fg: Option<u32>,
+    pub bg: Option<u32>,
+}
+
+// the parameters of the reply to a query like "\x1b[?...c" or "\x1b]11;...",
+// up to the reply's `end`
+fn reply<'r>(replies: &'r [u8], start: &[u8], end: &[u8]) -> Option<&'r [u8]> {
+    let at = replies.windows(start.len()).position(|w| w == start)? + start.len();
+    let rest = &replies[at..];
+    let len = end
+        .iter()
+        .filter_map(|&e| rest.iter().position(|&b| b == e))
+        .min()?;
+    Some(&rest[..len])
+}
+
+// 'rgb:RRRR/GGGG/BBBB', with one to four hex digits a component
+fn parse_rgb(color: &[u8]) -> Option<u32> {
+    let color = std::str::from_utf8(color).ok()?.strip_prefix("rgb:")?;
+    let mut rgb = 0;
+    for component in color.split('/').take(3) {
+        let max = 16u32.checked_pow(u32::try_from(component.len()).ok()?)? - 1;
+        let v = u32::from_str_radix(component, 16).ok()?;
+        rgb = rgb << 8 | ((v * 255 + max / 2) / max.max(1));
+    }
+    Some(rgb)
+}
+
+/// asks the terminal on stdout whether it shows sixel or kitty images, its
+/// cell size and colors. None if it isn't a terminal or didn't answer
+pub fn caps() -> Option<Caps> {
+    if !isatty(1) {
+        return None;
+    }
+    let _term = Term::raw().ok()?;
+    // a tiny kitty image query, the cell size, the text and background colors
+    // and then the device attributes. every terminal answers the last, and
+    // answers in order, so the others were ignored if it comes without them
+    print!(
+        "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[16t\x1b]10;?\x1b\\\x1b]11;?\x1b\\\x1b[c"
+    );
+    std::io::stdout().flush().ok()?;
+    let mut replies = vec![];
+    let start = std::time::Instant::now();
+    while reply(&replies, b"\x1b[?", b"c").is_none() {
+        if start.elapsed() > std::time::Duration::from_secs(1) {
+            return None;
+        }
+        let mut buf = [0; 256];
+        let n = std::io::stdin().read(&mut buf).ok()?;
+        replies.extend_from_slice(&buf[..n]);
+    }
+
+    let attributes = reply(&replies, b"\x1b[?", b"c")?;
+    let sixel = attributes.split(|&b| b == b';').any(|a| a == b"4");
+    let kitty =
+        reply(&replies, b"\x1b_Gi=31;", b"\x1b").is_some_and(|status| status.starts_with(b"OK"));
+    let number = |n: &[u8]| std::str::from_utf8(n).ok()?.parse::<u16>().ok();
+    // the pixels the kernel knows of, or the terminal's reply "\x1b[6;H;Wt"
+    let mut winsz = libc::winsize {
+        ws_col: 0,
+        ws_row: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: just ffi
+    unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut winsz as *mut _) };
+    let cell = if winsz.ws_xpixel > 0 && winsz.ws_col > 0 && winsz.ws_row > 0 {
+        Some((
+            winsz.ws_xpixel / winsz.ws_col,
+            winsz.ws_ypixel / winsz.ws_row,
+        ))
+    } else {
+        reply(&replies, b"\x1b[6;", b"t").and_then(|size| {
+            let mut size = size.split(|&b| b == b';');
+            let height = number(size.next()?)?;
+            Some((number(size.next()?)?, height))
+        })
+    };
+    let color = |osc: &[u8]| parse_rgb(reply(&replies, osc, b"\x1b\x07")?);
+    Some(Caps {
+        sixel,
+        kitty,
+        cell: cell.filter(|&(w, h)| w > 0 && h > 0),
+        fg: color(b"\x1b]10;"),
+        bg: color(b"\x1b]11;"),
+    })
+}
@@ -0,0 +1,557 @@
+// civil calendar conversions are from Howard Hinnant's date algorithms
+// http://howardhinnant.github.io/date_algorithms.html
+// they work on the proleptic gregorian calendar, which is what grafana and
+// RFC 3339 assume.
+
+use crate::tz::Tz;
+
+/// days since 1970-01-01 for the date `year-month-day`
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let (m, d) = (i64::from(month), i64::from(day));
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) for a number of days since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// 0 is sunday, 1970-01-01 was a thursday
+pub fn weekday(days: i64) -> u32 {
+    (days + 4).rem_euclid(7) as u32
+}
+
+pub fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+pub fn timestamp<'b>(time_ms: i64, tz: &Tz, buf: &'b mut [u8; 9]) -> &'b str {
+    let time = time_ms.div_euclid(1000);
+    let secs = (time + tz.offset(time)).rem_euclid(60 * 60 * 24);
+    let (hour, minute, second) = (secs / 3600, secs / 60 % 60, secs % 60);
+    for (i, n) in [hour, minute, second].into_iter().enumerate() {
+        buf[i * 3] = b'0' + (n / 10) as u8;
+        buf[i * 3 + 1] = b'0' + (n % 10) as u8;
+        if i < 2 {
+            buf[i * 3 + 2] = b':';
+        }
+    }
+
+    // SAFETY: only ascii digits and ':' were written to the first 8 bytes
+    unsafe { std::str::from_utf8_unchecked(&buf[..8]) }
+}
+
+/// 'YYYY-MM-DD HH:MM:SS' in `tz` for `time` in seconds since the epoch
+pub fn datetime(time: i64, tz: &Tz) -> String {
+    let time = time + tz.offset(time);
+    let (year, month, day) = civil_from_days(time.div_euclid(60 * 60 * 24));
+    let mut buf = [b' '; 9];
+    let hms = timestamp(time * 1000, &Tz::utc(), &mut buf);
+    format!("{year:04}-{month:02}-{day:02} {hms}")
+}
+
+/// RFC 3339 in `tz` for `time` in seconds since the epoch, like
+/// '2016-02-01T13:04:05+01:00'
+pub fn rfc3339(time: i64, tz: &Tz) -> String {
+    let offset = tz.offset(time);
+    let datetime = datetime(time, tz).replace(' ', "T");
+    if offset == 0 {
+        return format!("{datetime}Z");
+    }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (hours, minutes) = (offset.abs() / 3600, offset.abs() / 60 % 60);
+    format!("{datetime}{sign}{hours:02}:{minutes:02}")
+}
+
+struct Cursor<'s>(&'s [u8]);
+
+impl Cursor<'_> {
+    fn num(&mut self, digits: usize) -> Option<u32> {
+        if self.0.len() < digits || !self.0[..digits].iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let (n, rest) = self.0.split_at(digits);
+        self.0 = rest;
+        Some(n.iter().fold(0, |acc, d| acc * 10 + u32::from(d - b'0')))
+    }
+
+    fn eat(&mut self, any: &[u8]) -> Option<u8> {
+        match self.0.first() {
+            Some(c) if any.contains(c) => {
+                self.0 = &self.0[1..];
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// parses an absolute datetime, returning seconds since the unix epoch.
+/// accepts RFC 3339 ('2016-02-01T13:04:05.123+01:00'), the condensed ISO 8601
+/// form ('20160201T130405'), 'YYYY-MM-DD HH:MM\[:SS\]', and a date alone
+/// ('2016-02-01'). a missing offset means UTC.
+pub fn parse_datetime(time_s: &str) -> Option<i64> {
+    let mut c = Cursor(time_s.as_bytes());
+    let year = i64::from(c.num(4)?);
+    let extended = c.eat(b"-").is_some();
+    let month = c.num(2)?;
+    if extended {
+        c.eat(b"-")?;
+    }
+    let day = c.num(2)?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let date = days_from_civil(year, month, day) * 60 * 60 * 24;
+    if c.done() {
+        return Some(date);
+    }
+
+    c.eat(b"Tt ")?;
+    let hour = c.num(2)?;
+    if extended {
+        c.eat(b":")?;
+    }
+    let minute = c.num(2)?;
+    // seconds are optional in both forms ('2016-02-01 13:04', '20160201T1304')
+    let second = if extended {
+        match c.eat(b":") {
+            Some(_) => c.num(2)?,
+            None => 0,
+        }
+    } else {
+        c.num(2).unwrap_or(0)
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // we only deal in whole seconds, so fractions are dropped
+    if c.eat(b".,").is_some() {
+        c.num(1)?;
+        while c.num(1).is_some() {}
+    }
+    let offset = match c.eat(b"Zz+-") {
+        None | Some(b'Z' | b'z') => 0,
+        Some(sig) => {
+            let oh = c.num(2)?;
+            c.eat(b":");
+            let om = c.num(2)?;
+            if oh > 23 || om > 59 {
+                return None;
+            }
+            let offset = i64::from(oh * 60 * 60 + om * 60);
+            if sig == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+    if !c.done() {
+        return None;
+    }
+
+    // leap seconds get folded into the following second
+    let time = i64::from(hour * 60 * 60 + minute * 60 + second);
+    Some(date + time - offset)
+}
+
+// anything larger is taken to be epoch milliseconds, this is ~5000AD in seconds
+const EPOCH_MS_THRESHOLD: i64 = 100_000_000_000;
+
+/// seconds in one of grafana's duration units, months and years are
+/// approximate here, see `add_months` for calendar arithmetic
+fn unit_secs(unit: char) -> Option<i64> {
+    Some(match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 60 * 60 * 24,
+        'w' => 60 * 60 * 24 * 7,
+        'M' => 60 * 60 * 24 * 30,
+        'y' => 60 * 60 * 24 * 365,
+        _ => return None,
+    })
+}
+
+/// parses a grafana duration like '5s', '1m', '1h' or '7d' into seconds
+pub fn parse_duration(dur_s: &str) -> Option<i64> {
+    let dur_s = dur_s.trim();
+    let n = dur_s
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(dur_s.len());
+    let (n, unit) = dur_s.split_at(n);
+    let mut unit = unit.trim_start().chars();
+    match (n.parse::<i64>().ok()?, unit.next(), unit.next()) {
+        (n, Some(u), None) => n.checked_mul(unit_secs(u)?),
+        (n, None, None) => Some(n),
+        _ => None,
+    }
+}
+
+// None when that's further out than seconds go
+fn add_months(time: i64, months: i64) -> Option<i64> {
+    let (days, secs) = (time.div_euclid(60 * 60 * 24), time.rem_euclid(60 * 60 * 24));
+    let (year, month, day) = civil_from_days(days);
+    let m0 = (year * 12 + i64::from(month) - 1).checked_add(months)?;
+    let (year, month) = (m0.div_euclid(12), m0.rem_euclid(12) as u32 + 1);
+    // days_from_civil doesn't check, so years past that are turned away here
+    year.checked_mul(366 * 60 * 60 * 24)?;
+    let day = day.min(days_in_month(year, month));
+    (days_from_civil(year, month, day) * 60 * 60 * 24).checked_add(secs)
+}
+
+// the utc time at which it's `local` in `tz`, using the offset in effect
+// just before that local time for the hour that daylight saving repeats
+fn from_local(local: i64, tz: &Tz) -> Option<i64> {
+    let guess = local.checked_sub(tz.offset(local))?;
+    local.checked_sub(tz.offset(guess))
+}
+
+// rounds `time` down to the start of `unit` in `tz`, or up to its last second
+fn round(time: i64, unit: char, up: bool, tz: &Tz) -> Option<i64> {
+    let day = 60 * 60 * 24;
+    let local = time.checked_add(tz.offset(time))?;
+    let start = match unit {
+        's' | 'm' | 'h' | 'd' => {
+            let unit = unit_secs(unit)?;
+            local - local.rem_euclid(unit)
+        }
+        // grafana weeks start on monday
+        'w' => {
+            let days = local.div_euclid(day);
+            (days - i64::from((weekday(days) + 6) % 7)) * day
+        }
+        'M' | 'y' => {
+            let (year, month, _) = civil_from_days(local.div_euclid(day));
+            let month = if unit == 'y' { 1 } else { month };
+            days_from_civil(year, month, 1) * day
+        }
+        _ => return None,
+    };
+    if !up {
+        return from_local(start, tz);
+    }
+    let end = match unit {
+        'M' => add_months(start, 1)?,
+        'y' => add_months(start, 12)?,
+        _ => start.checked_add(unit_secs(unit)?)?,
+    };
+    Some(from_local(end, tz)? - 1)
+}
+
+// grafana date math, 'now', 'now-5m', 'now-1d/d', 'now/w+1d'. rounding is
+// done in `tz`, so 'now/d' starts at the dashboard's midnight.
+fn parse_relative(time_s: &str, now: i64, round_up: bool, tz: &Tz) -> Option<i64> {
+    let mut time = now;
+    let mut rest = time_s.strip_prefix("now")?.trim_start();
+    while let Some(op) = rest.chars().next() {
+        rest = rest[op.len_utf8()..].trim_start();
+        let n = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (num, r) = rest.split_at(n);
+        let r = r.trim_start();
+        let unit = r.chars().next()?;
+        rest = r[unit.len_utf8()..].trim_start();
+        match op {
+            '+' | '-' => {
+                let n: i64 = if num.is_empty() { 1 } else { num.parse().ok()? };
+                let n = if op == '-' { -n } else { n };
+                time = match unit {
+                    'M' => add_months(time, n)?,
+                    'y' => add_months(time, n.checked_mul(12)?)?,
+                    _ => time.checked_add(n.checked_mul(unit_secs(unit)?)?)?,
+                };
+            }
+            '/' if num.is_empty() => time = round(time, unit, round_up, tz)?,
+            _ => return None,
+        }
+    }
+    Some(time)
+}
+
+fn parse(time_s: &str, now: i64, round_up: bool, tz: &Tz) -> Option<i64> {
+    let time_s = time_s.trim();
+    if !time_s.is_empty() && time_s.chars().all(|c| c.is_ascii_digit()) {
+        let time: i64 = time_s.parse().ok()?;
+        if time >= EPOCH_MS_THRESHOLD {
+            Some(time / 1000)
+        } else {
+            Some(time)
+        }
+    } else if time_s.starts_with("now") {
+        parse_relative(time_s, now, round_up, tz)
+    } else {
+        parse_datetime(time_s)
+    }
+}
+
+/// parses the start of a time range, grafana relative times are rounded down
+/// in `tz` ('now/d' is the start of today)
+pub fn parse_instant(time_s: &str, now: i64, tz: &Tz) -> Option<i64> {
+    parse(time_s, now, false, tz)
+}
+
+/// parses the end of a time range, grafana relative times are rounded up
+/// in `tz` ('now/d' is the end of today)
+pub fn parse_instant_end(time_s: &str, now: i64, tz: &Tz) -> Option<i64> {
+    parse(time_s, now, true, tz)
+}
+
+// https://github.com/grafana/grafana/blob/main/packages/grafana-data/src/datetime/rangeutil.ts
+/// rounds an interval in milliseconds to one of grafana's "nice" steps
+pub fn round_interval(interval_ms: i64) -> i64 {
+    const STEPS: &[(i64, i64)] = &[
+        (15, 10),
+        (35, 20),
+        (75, 50),
+        (150, 100),
+        (350, 200),
+        (750, 500),
+        (1500, 1000),
+        (3500, 2000),
+        (7500, 5000),
+        (12500, 10000),
+        (17500, 15000),
+        (25000, 20000),
+        (45000, 30000),
+        (90000, 60000),
+        (210000, 120000),
+        (450000, 300000),
+        (750000, 600000),
+        (1050000, 900000),
+        (1500000, 1200000),
+        (2700000, 1800000),
+        (5400000, 3600000),
+        (9000000, 7200000),
+        (16200000, 10800000),
+        (32400000, 21600000),
+        (86400000, 43200000),
+        (604800000, 86400000),
+        (1814400000, 604800000),
+        (3628800000, 2592000000),
+    ];
+    STEPS
+        .iter()
+        .find(|&&(below, _)| interval_ms < below)
+        .map_or(31536000000, |&(_, step)| step)
+}
+
+/// the interval grafana would query a range with for `max_data_points`,
+/// never smaller than the panel's min interval `low_limit_ms`
+pub fn calculate_interval(range_ms: i64, max_data_points: i64, low_limit_ms: i64) -> i64 {
+    let interval_ms = round_interval(range_ms / max_data_points.max(1));
+    interval_ms.max(low_limit_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days() {
+        for (date, days) in [
+            ((1970, 1, 1), 0),
+            ((1969, 12, 31), -1),
+            ((2000, 2, 29), 11016),
+            ((2024, 2, 29), 19782),
+            ((1900, 3, 1), -25508),
+            ((1600, 3, 1), -135080),
+            ((1, 1, 1), -719162),
+        ] {
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days, "{date:?}");
+            assert_eq!(civil_from_days(days), date, "{days}");
+        }
+        // 1969-12-28 was a sunday
+        assert_eq!(weekday(-4), 0);
+        assert_eq!(weekday(0), 4);
+    }
+
+    #[test]
+    fn leap_years() {
+        for (year, leap) in [(1900, false), (2000, true), (2023, false), (2024, true)] {
+            assert_eq!(is_leap_year(year), leap, "{year}");
+            assert_eq!(days_in_month(year, 2), if leap { 29 } else { 28 }, "{year}");
+        }
+        assert_eq!(days_in_month(2023, 4), 30);
+        assert_eq!(days_in_month(2023, 12), 31);
+    }
+
+    #[test]
+    fn parse_datetimes() {
+        for (s, time) in [
+            ("2016-02-01T13:04:05+01:00", Some(1454328245)),
+            ("2016-02-01T13:04:05.123+01:00", Some(1454328245)),
+            ("2016-02-01t12:04:05z", Some(1454328245)),
+            ("2016-02-01T13:04:05-05:30", Some(1454351645)),
+            ("2016-02-01T17:34:05+0530", Some(1454328245)),
+            ("20160201T120405", Some(1454328245)),
+            ("20160201T1304", Some(1454331840)),
+            ("2016-02-01 13:04", Some(1454331840)),
+            ("2016-02-01", Some(1454284800)),
+            ("20160201", Some(1454284800)),
+            ("1969-07-20T20:17:40Z", Some(-14182940)),
+            ("2000-02-29", Some(951782400)),
+            ("1900-02-29", None),
+            ("2023-02-29", None),
+            ("2016-13-01", None),
+            ("2016-02-01T24:00", None),
+            ("2016-02-01T13:04:05+24:00", None),
+            ("2016-02-01T13:04:05.", None),
+            ("2016-02-01T13:04:05 ", None),
+            ("2016-0201", None),
+            ("yesterday", None),
+        ] {
+            assert_eq!(parse_datetime(s), time, "{s}");
+        }
+    }
+
+    #[test]
+    fn format_rfc3339() {
+        let utc = Tz::utc();
+        assert_eq!(rfc3339(1454328245, &utc), "2016-02-01T12:04:05Z");
+        assert_eq!(rfc3339(-14182940, &utc), "1969-07-20T20:17:40Z");
+        assert_eq!(rfc3339(-1, &utc), "1969-12-31T23:59:59Z");
+        let india = Tz::posix("IST-5:30").unwrap();
+        assert_eq!(rfc3339(1454328245, &india), "2016-02-01T17:34:05+05:30");
+        let newfoundland = Tz::posix("NST3:30").unwrap();
+        assert_eq!(
+            rfc3339(1454328245, &newfoundland),
+            "2016-02-01T08:34:05-03:30"
+        );
+        for time in [1454328245, -14182940, 0] {
+            for tz in [&utc, &india, &newfoundland] {
+                assert_eq!(parse_datetime(&rfc3339(time, tz)), Some(time));
+            }
+        }
+        let mut buf = [b' '; 9];
+        assert_eq!(timestamp(-1000, &utc, &mut buf), "23:59:59");
+    }
+
+    #[test]
+    fn relative() {
+        // a monday, 2016-02-01T12:04:05Z
+        let now = 1454328245;
+        for (s, time) in [
+            ("now", Some(now)),
+            ("now-5m", Some(now - 300)),
+            ("now - 1h", Some(now - 3600)),
+            ("now-M", Some(1451649845)),
+            ("now+1y", Some(1485950645)),
+            ("now/d", Some(1454284800)),
+            ("now-1d/d", Some(1454198400)),
+            ("now/w+1d", Some(1454371200)),
+            ("now/M", Some(1454284800)),
+            ("now/y", Some(1451606400)),
+            ("now-5x", None),
+            ("now/5m", None),
+            ("now*1d", None),
+            ("nowé", None),
+            ("now-5é", None),
+            ("now-é", None),
+        ] {
+            assert_eq!(parse_instant(s, now, &Tz::utc()), time, "{s}");
+        }
+        assert_eq!(
+            parse_instant_end("now/d", now, &Tz::utc()),
+            Some(1454371199)
+        );
+        assert_eq!(
+            parse_instant_end("now/M", now, &Tz::utc()),
+            Some(1456790399)
+        );
+    }
+
+    #[test]
+    fn relative_rounding_in_tz() {
+        // 2016-02-01T23:04:05+11:00 in sydney, 07:04:05-05:00 in new york
+        let now = 1454328245;
+        let sydney = Tz::posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(parse_instant("now/d", now, &sydney), Some(1454245200));
+        assert_eq!(parse_instant_end("now/d", now, &sydney), Some(1454331599));
+        assert_eq!(parse_instant("now/w", now, &sydney), Some(1454245200));
+        assert_eq!(parse_instant("now/M", now, &sydney), Some(1454245200));
+        assert_eq!(parse_instant("now/y", now, &sydney), Some(1451566800));
+        let new_york = Tz::posix("EST5EDT").unwrap();
+        assert_eq!(parse_instant("now/d", now, &new_york), Some(1454302800));
+
+        // the day daylight saving starts is 23 hours long
+        let now = 1710072000;
+        assert_eq!(parse_instant("now/d", now, &new_york), Some(1710046800));
+        assert_eq!(parse_instant_end("now/d", now, &new_york), Some(1710129599));
+    }
+
+    #[test]
+    fn too_far_out() {
+        let now = 1454328245;
+        let sydney = Tz::posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        for s in [
+            "now-99999999999999y",
+            "now+99999999999999y",
+            "now-999999999999999999M",
+            "now-9223372036854775807s-9223372036854775807s",
+            "now+9999999999999999w",
+            "now-106751991167300d-106751991167300d",
+        ] {
+            assert_eq!(parse_instant(s, now, &Tz::utc()), None, "{s}");
+            assert_eq!(parse_instant_end(s, now, &sydney), None, "{s}");
+        }
+        // as far as the calendar goes still rounds
+        for s in ["now-290000000000y/y", "now+290000000000y/d"] {
+            assert!(parse_instant_end(s, now, &sydney).is_some(), "{s}");
+        }
+        let s = format!("now+{}s/d", i64::MAX - now);
+        assert_eq!(parse_instant(&s, now, &sydney), None);
+        assert_eq!(parse_duration("9999999999999999y"), None);
+        assert_eq!(parse_duration("9223372036854775807"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn intervals() {
+        for (interval, rounded) in [
+            (0, 10),
+            (14, 10),
+            (15, 20),
+            (1499, 1000),
+            (1500, 2000),
+            (21600, 20000),
+            (86_399_999, 43_200_000),
+            (86_400_000, 86_400_000),
+            (3_628_800_000, 31_536_000_000),
+        ] {
+            assert_eq!(round_interval(interval), rounded, "{interval}");
+        }
+        let six_hours = 6 * 60 * 60 * 1000;
+        assert_eq!(calculate_interval(six_hours, 1000, 0), 20000);
+        assert_eq!(calculate_interval(six_hours, 1000, 60000), 60000);
+        assert_eq!(calculate_interval(six_hours, 0, 0), 21_600_000);
+    }
+}
@@ -0,0 +1,328 @@
+// full screen mode, drawn on the alternate screen with the terminal in raw
+// mode so single keys can be read. each change re-runs the panel's query and
+// redraws the whole screen in place.
+
+use std::collections::HashMap;
+use std::io::Write as _;
+
+use graf::client::Client;
+use graf::color::{self, series_colors};
+use graf::models::{Dashboard, Panel};
+use graf::query::{build_query, panel_time, variable_options, Query};
+use graf::render::Chart;
+use graf::time::{datetime, parse_instant, parse_instant_end};
+use graf::tz::Tz;
+
+use crate::term::{read_key, winsize, Key, Term};
+
+const HELP: &str =
+    "up/down panel  left/right pan  +/- zoom  0 reset  tab/v variable  t target  1-9 series  r refresh  q quit";
+
+pub struct Options<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub interval: Option<i64>,
+    pub compare: Option<i64>,
+    // refresh every `refresh` seconds
+    pub refresh: Option<i64>,
+    pub tz: &'a Tz,
+    pub color: color::Mode,
+    // the panel to start on, of the dashboard's queried panels
+    pub panel: usize,
+}
+
+struct State {
+    panel: usize,
+    target: usize,
+    // the variable tab and v cycle through
+    var: usize,
+    values: Vec<usize>,
+    // the range is scaled by 2^zoom around its middle, then moved by pan seconds
+    zoom: i32,
+    pan: i64,
+    hidden: Vec<bool>,
+}
+
+pub fn run(client: &Client, dash: &Dashboard, opts: Options) -> Result<(), String> {
+    let panels = dash.queried_panels();
+    if panels.is_empty() {
+        return Err("dashboard has no panels with queries".to_string());
+    }
+
+    let now = || {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        i64::try_from(now).unwrap()
+    };
+    let range = |now: i64| match (
+        parse_instant(opts.from, now, opts.tz),
+        parse_instant_end(opts.to, now, opts.tz),
+    ) {
+        (Some(from), Some(to)) => Ok((from, to)),
+        _ => Err(format!("invalid time range {} to {}", opts.from, opts.to)),
+    };
+
+    // variable options are looked up once, over the dashboard's range
+    let (from, to) = range(now())?;
+    let variables: Vec<(String, Vec<String>)> = dash
+        .variables
+        .iter()
+        .map(|var| {
+            let options = variable_options(client, var, from, to).unwrap_or_else(|err| {
+                eprintln!(
+                    "warning: unable to look up the values of ${}: {err}",
+                    var.name
+                );
+                vec![]
+            });
+            (var.name.clone(), options)
+        })
+        .collect();
+    let values = dash
+        .variables
+        .iter()
+        .zip(&variables)
+        .map(|(var, (_, options))| {
+            options
+                .iter()
+                .position(|o| var.current.first() == Some(o))
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut state = State {
+        panel: opts.panel.min(panels.len() - 1),
+        target: 0,
+        var: 0,
+        values,
+        zoom: 0,
+        pan: 0,
+        hidden: vec![],
+    };
+    let _term = Term::fullscreen().map_err(|err| format!("--tui: {err}"))?;
+    crate::watch_resize();
+    let mut last_draw = std::time::Instant::now();
+    let mut dirty = true;
+    loop {
+        let stale = opts
+            .refresh
+            .is_some_and(|r| last_draw.elapsed().as_secs() >= r.max(1) as u64);
+        if dirty || stale || crate::resized() {
+            let now = now();
+            let (from, to) = range(now)?;
+            draw(client, &panels, &variables, &state, (now, from, to), &opts)?;
+            last_draw = std::time::Instant::now();
+            dirty = false;
+        }
+
+        let Some(key) = read_key() else {
+            continue;
+        };
+        dirty = true;
+        let panel = panels[state.panel];
+        match key {
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => return Ok(()),
+            Key::Up | Key::Char('k') => {
+                state.panel = (state.panel + panels.len() - 1) % panels.len();
+                (state.target, state.hidden) = (0, vec![]);
+            }
+            Key::Down | Key::Char('j') => {
+                state.panel = (state.panel + 1) % panels.len();
+                (state.target, state.hidden) = (0, vec![]);
+            }
+            // pan by half the visible range
+            Key::Left | Key::Char('h') | Key::Right | Key::Char('l') => {
+                let (from, to) = range(now())?;
+                let span = scaled_span(to - from, state.zoom);
+                let step = (span / 2).max(1);
+                if matches!(key, Key::Left | Key::Char('h')) {
+                    state.pan -= step;
+                } else {
+                    state.pan += step;
+                }
+            }
+            Key::Char('+' | '=') => state.zoom -= 1,
+            Key::Char('-' | '_') => state.zoom += 1,
+            Key::Char('0') => (state.zoom, state.pan) = (0, 0),
+            Key::Tab if !variables.is_empty() => state.var = (state.var + 1) % variables.len(),
+            Key::Char('v') if !variables.is_empty() => {
+                let n = variables[state.var].1.len().max(1);
+                state.values[state.var] = (state.values[state.var] + 1) % n;
+            }
+            Key::Char('t') => {
+                let n = panel.targets().map_or(1, |ts| ts.len().max(1));
+                state.target = (state.target + 1) % n;
+                state.hidden = vec![];
+            }
+            Key::Char(c @ '1'..='9') => {
+                let k = usize::from(c as u8 - b'1');
+                if state.hidden.len() <= k {
+                    state.hidden.resize(k + 1, false);
+                }
+                state.hidden[k] = !state.hidden[k];
+            }
+            Key::Char('r') => {}
+            _ => dirty = false,
+        }
+    }
+}
+
+fn scaled_span(span: i64, zoom: i32) -> i64 {
+    let span = span as f64 * 2f64.powi(zoom);
+    (span as i64).max(1)
+}
+
+fn draw(
+    client: &Client,
+    panels: &[&Panel],
+    variables: &[(String, Vec<String>)],
+    state: &State,
+    (now, from, to): (i64, i64, i64),
+    opts: &Options,
+) -> Result<(), String> {
+    let (rows, cols) = winsize(1);
+    // title, variables, legend and help lines around the chart
+    let height = rows - 4;
+    let panel = panels[state.panel];
+    let targets = panel.targets().unwrap_or_default();
+    let target = &targets[state.target.min(targets.len() - 1)];
+    let templating: HashMap<_, _> = variables
+        .iter()
+        .zip(&state.values)
+        .filter_map(|((name, options), &v)| Some((name.clone(), options.get(v)?.clone())))
+        .collect();
+
+    let mut pt = panel_time(panel, from, to, now, i64::from(height) + 1, opts.tz);
+    let span = scaled_span(pt.to - pt.from, state.zoom);
+    let middle = pt.from + (pt.to - pt.from) / 2 + state.pan;
+    (pt.from, pt.to) = (middle - span / 2, middle + span / 2);
+    let interval = pt.interval(opts.interval);
+    let fetched = build_query(
+        client,
+        panel,
+        target,
+        &templating,
+        pt.max_data_points,
+        interval,
+    )
+    .and_then(|query| {
+        let query = Query {
+            client,
+            query,
+            refid: target.ref_id.clone(),
+            interval,
+            compare: opts.compare,
+        };
+        query.fetch(pt.from, pt.to)
+    });
+    let e = |err: std::io::Error| err.to_string();
+    let fetched = match fetched {
+        Ok(fetched) => fetched,
+        // grafana may be back by the next refresh, the last screen stays up
+        // until then with the error in place of the help
+        Err(failure) => {
+            let retry = match opts.refresh {
+                Some(r) => format!("retrying every {}s", r.max(1)),
+                None => "r to retry".to_string(),
+            };
+            let status: String = format!("failed, {retry}: {failure}")
+                .chars()
+                .take(usize::from(cols))
+                .collect();
+            let mut stdout = std::io::stdout().lock();
+            write!(stdout, "\x1b[{rows};1H\x1b[7m{status}\x1b[0m\x1b[K").map_err(e)?;
+            return stdout.flush().map_err(e);
+        }
+    };
+
+    let mut out = vec![];
+    let w = &mut out;
+    write!(w, "\x1b[H").map_err(e)?;
+
+    let title = &panel.title;
+    write!(
+        w,
+        "\x1b[1m{title}\x1b[0m [{}/{}] {} to {}",
+        state.panel + 1,
+        panels.len(),
+        datetime(pt.from, opts.tz),
+        datetime(pt.to, opts.tz),
+    )
+    .map_err(e)?;
+    if !pt.info.is_empty() && !panel.hide_time_override {
+        write!(w, " ({})", pt.info.join(", ")).map_err(e)?;
+    }
+    if targets.len() > 1 {
+        write!(w, " target {}", target.ref_id).map_err(e)?;
+    }
+    writeln!(w, "\x1b[K").map_err(e)?;
+
+    for (k, ((name, options), &v)) in variables.iter().zip(&state.values).enumerate() {
+        let value = options.get(v).map_or("", String::as_str);
+        if k == state.var {
+            write!(w, "\x1b[7m${name}={value}\x1b[0m  ").map_err(e)?;
+        } else {
+            write!(w, "${name}={value}  ").map_err(e)?;
+        }
+    }
+    writeln!(w, "\x1b[K").map_err(e)?;
+
+    // notices go after the legend, the line is cut off where it has to be
+    let notices: String = (pt.warnings.iter().map(|w| format!("  warning: {w}")))
+        .chain(fetched.notices.iter().map(|n| format!("  {n}")))
+        .collect();
+    match fetched.data {
+        Ok(Some(fetched)) => {
+            let hidden = (0..fetched.ncurrent)
+                .map(|k| state.hidden.get(k).copied().unwrap_or(false))
+                .collect::<Vec<_>>();
+            let colors = series_colors(opts.color, panel, &fetched.names, &fetched.colors);
+            for (k, name) in fetched.names.iter().enumerate().take(fetched.ncurrent) {
+                let n = if k < 9 {
+                    format!("{}", k + 1)
+                } else {
+                    " ".to_string()
+                };
+                if hidden[k] {
+                    write!(w, "\x1b[2m{n} {name}\x1b[0m  ").map_err(e)?;
+                } else if let Some(color) = colors.get(k) {
+                    write!(w, "{n} \x1b[{color}m{name}\x1b[0m  ").map_err(e)?;
+                } else {
+                    write!(w, "{n} {name}  ").map_err(e)?;
+                }
+            }
+            writeln!(w, "\x1b[2m{notices}\x1b[0m\x1b[K").map_err(e)?;
+
+            // make room for time stamps "13:04:05 "
+            let chart = Chart::new(
+                fetched.times,
+                fetched.vals,
+                fetched.ncurrent,
+                cols - 9,
+                hidden,
+                colors,
+            );
+            let n = chart.times.len();
+            let first = n.saturating_sub(usize::from(height)).max(1);
+            for i in first..n {
+                chart
+                    .write_row(w, i, i == first, (i - first) % 5 == 0, opts.tz)
+                    .map_err(e)?;
+                writeln!(w, "\x1b[K").map_err(e)?;
+            }
+        }
+        Ok(None) => write!(w, "\x1b[2m{notices}\x1b[0m\x1b[K\nno data\x1b[K\n").map_err(e)?,
+        Err(err) => write!(
+            w,
+            "\x1b[2m{notices}\x1b[0m\x1b[K\nquery failed: {err}\x1b[K\n"
+        )
+        .map_err(e)?,
+    }
+    write!(w, "\x1b[J\x1b[{rows};1H\x1b[2m{HELP}\x1b[0m\x1b[K").map_err(e)?;
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&out).map_err(e)?;
+    stdout.flush().map_err(e)
+}
@@ -0,0 +1,518 @@
+// timezones are read straight from the system zoneinfo (TZif, RFC 8536) files
+// rather than going through libc's localtime, which depends on the process-wide
+// TZ variable.
+
+use crate::time::{civil_from_days, days_from_civil, days_in_month, is_leap_year, weekday};
+
+const DAY: i64 = 60 * 60 * 24;
+
+#[derive(Clone, Debug)]
+pub struct Tz {
+    name: String,
+    // (utc time the offset starts applying, offset in seconds east of UTC)
+    transitions: Vec<(i64, i64)>,
+    // offset before the first transition
+    initial: i64,
+    // applies after the last transition
+    rule: Option<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    std_offset: i64,
+    dst: Option<(i64, RuleDate, i64, RuleDate, i64)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum RuleDate {
+    // Jn: 1-based day of year, february 29th is never counted
+    Julian1(u32),
+    // n: 0-based day of year, february 29th is counted
+    Julian0(u32),
+    // Mm.w.d: day d (0 is sunday) of week w (5 is last) of month m
+    MonthWeekDay(u32, u32, u32),
+}
+
+impl Tz {
+    pub fn utc() -> Tz {
+        Tz {
+            name: "UTC".to_string(),
+            transitions: vec![],
+            initial: 0,
+            rule: None,
+        }
+    }
+
+    /// the system's local timezone, according to TZ or /etc/localtime
+    pub fn local() -> Tz {
+        let tz = match std::env::var("TZ") {
+            Ok(tz) if tz.is_empty() => return Tz::utc(),
+            Ok(tz) => {
+                let name = tz.strip_prefix(':').unwrap_or(&tz);
+                Tz::zoneinfo(name).ok().or_else(|| Tz::posix(name))
+            }
+            Err(_) => std::fs::read("/etc/localtime")
+                .ok()
+                .and_then(|data| Tz::from_tzif("local", &data)),
+        };
+        tz.unwrap_or_else(Tz::utc)
+    }
+
+    /// an IANA timezone like 'Europe/Berlin', or one of the names grafana uses
+    /// for dashboard timezones ('utc', 'browser' which is local, or empty)
+    pub fn named(name: &str) -> Result<Tz, String> {
+        match name {
+            "" | "browser" | "local" => Ok(Tz::local()),
+            "utc" | "UTC" => Ok(Tz::utc()),
+            _ => Tz::zoneinfo(name),
+        }
+    }
+
+    fn zoneinfo(name: &str) -> Result<Tz, String> {
+        if name.starts_with('/') || name.split('/').any(|p| p == ".." || p.is_empty()) {
+            return Err(format!("invalid timezone name {name:?}"));
+        }
+        let dir = std::env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string());
+        let path = std::path::Path::new(&dir).join(name);
+        let data = std::fs::read(&path)
+            .map_err(|err| format!("unknown timezone {name:?} ({}): {err}", path.display()))?;
+        Tz::from_tzif(name, &data)
+            .ok_or_else(|| format!("invalid zoneinfo file {}", path.display()))
+    }
+
+    /// a timezone from a POSIX TZ string like 'CET-1CEST,M3.5.0,M10.5.0/3'
+    pub(crate) fn posix(name: &str) -> Option<Tz> {
+        let rule = Rule::parse(name)?;
+        Some(Tz {
+            name: name.to_string(),
+            transitions: vec![],
+            initial: rule.std_offset,
+            rule: Some(rule),
+        })
+    }
+
+    fn from_tzif(name: &str, data: &[u8]) -> Option<Tz> {
+        struct Header {
+            isutcnt: usize,
+            isstdcnt: usize,
+            leapcnt: usize,
+            timecnt: usize,
+            typecnt: usize,
+            charcnt: usize,
+        }
+        fn header(data: &[u8]) -> Option<(u8, Header)> {
+            if data.len() < 44 || &data[..4] != b"TZif" {
+                return None;
+            }
+            let n = |i: usize| {
+                let b = &data[20 + i * 4..24 + i * 4];
+                u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize
+            };
+            let hdr = Header {
+                isutcnt: n(0),
+                isstdcnt: n(1),
+                leapcnt: n(2),
+                timecnt: n(3),
+                typecnt: n(4),
+                charcnt: n(5),
+            };
+            Some((data[4], hdr))
+        }
+        fn block_len(h: &Header, time_len: usize) -> usize {
+            h.timecnt * time_len
+                + h.timecnt
+                + h.typecnt * 6
+                + h.charcnt
+                + h.leapcnt * (time_len + 4)
+                + h.isstdcnt
+                + h.isutcnt
+        }
+
+        let (version, mut hdr) = header(data)?;
+        let mut data = &data[44..];
+        let mut time_len = 4;
+        if version >= b'2' {
+            // skip the 32-bit block, the 64-bit one that follows covers more time
+            data = data.get(block_len(&hdr, 4)..)?;
+            hdr = header(data)?.1;
+            data = &data[44..];
+            time_len = 8;
+        }
+        if data.len() < block_len(&hdr, time_len) {
+            return None;
+        }
+
+        let (times, data) = data.split_at(hdr.timecnt * time_len);
+        let (idxs, data) = data.split_at(hdr.timecnt);
+        let (types, data) = data.split_at(hdr.typecnt * 6);
+        let offsets: Vec<i64> = types
+            .chunks(6)
+            .map(|t| i64::from(i32::from_be_bytes([t[0], t[1], t[2], t[3]])))
+            .collect();
+        let transitions = times
+            .chunks(time_len)
+            .zip(idxs)
+            .map(|(t, &i)| {
+                let t = match *t {
+                    [a, b, c, d] => i64::from(i32::from_be_bytes([a, b, c, d])),
+                    [a, b, c, d, e, f, g, h] => i64::from_be_bytes([a, b, c, d, e, f, g, h]),
+                    _ => unreachable!("chunks are time_len long"),
+                };
+                Some((t, *offsets.get(usize::from(i))?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let initial = *offsets.first()?;
+
+        let rule = if time_len == 8 {
+            // footer is '\n' POSIX-TZ '\n'
+            let footer = &data[hdr.charcnt + hdr.leapcnt * 12 + hdr.isstdcnt + hdr.isutcnt..];
+            std::str::from_utf8(footer)
+                .ok()
+                .and_then(|f| f.trim_matches('\n').lines().next())
+                .and_then(Rule::parse)
+        } else {
+            None
+        };
+
+        Some(Tz {
+            name: name.to_string(),
+            transitions,
+            initial,
+            rule,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// offset from UTC in seconds at the utc time `time`
+    pub fn offset(&self, time: i64) -> i64 {
+        let n = self.transitions.partition_point(|&(t, _)| t <= time);
+        match &self.rule {
+            // a zone from a TZ rule alone has no transitions at all
+            Some(rule) if n == self.transitions.len() => rule.offset(time),
+            _ if n == 0 => self.initial,
+            _ => self.transitions[n - 1].1,
+        }
+    }
+}
+
+impl Rule {
+    // https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/V1_chap08.html
+    // 'EST5EDT,M3.2.0,M11.1.0', '<+0330>-3:30', 'UTC0'
+    fn parse(s: &str) -> Option<Rule> {
+        fn name(s: &str) -> Option<&str> {
+            if let Some(s) = s.strip_prefix('<') {
+                return Some(&s[s.find('>')? + 1..]);
+            }
+            let n = s
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(s.len());
+            (n >= 3).then(|| &s[n..])
+        }
+        // [+-]hh[:mm[:ss]]
+        fn time(s: &str) -> Option<(i64, &str)> {
+            let (sig, s) = match s.as_bytes().first()? {
+                b'-' => (-1, &s[1..]),
+                b'+' => (1, &s[1..]),
+                _ => (1, s),
+            };
+            let mut secs = 0;
+            let mut rest = s;
+            for unit in [60 * 60, 60, 1] {
+                let n = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                if n == 0 {
+                    return None;
+                }
+                secs += rest[..n].parse::<i64>().ok()? * unit;
+                rest = &rest[n..];
+                match rest.strip_prefix(':') {
+                    Some(r) if unit > 1 => rest = r,
+                    _ => break,
+                }
+            }
+            Some((sig * secs, rest))
+        }
+        fn date(s: &str) -> Option<(RuleDate, i64, &str)> {
+            fn num(s: &str) -> Option<(u32, &str)> {
+                let n = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+                Some((s[..n].parse::<u32>().ok()?, &s[n..]))
+            }
+            let (date, s) = if let Some(s) = s.strip_prefix('J') {
+                let (n, s) = num(s)?;
+                ((1..=365).contains(&n).then_some(RuleDate::Julian1(n))?, s)
+            } else if let Some(s) = s.strip_prefix('M') {
+                let (m, s) = num(s)?;
+                let (w, s) = num(s.strip_prefix('.')?)?;
+                let (d, s) = num(s.strip_prefix('.')?)?;
+                let valid = (1..=12).contains(&m) && (1..=5).contains(&w) && d <= 6;
+                (valid.then_some(RuleDate::MonthWeekDay(m, w, d))?, s)
+            } else {
+                let (n, s) = num(s)?;
+                ((n <= 365).then_some(RuleDate::Julian0(n))?, s)
+            };
+            match s.strip_prefix('/') {
+                Some(s) => {
+                    let (t, s) = time(s)?;
+                    Some((date, t, s))
+                }
+                None => Some((date, 2 * 60 * 60, s)),
+            }
+        }
+
+        let s = name(s)?;
+        let (std, s) = time(s)?;
+        // POSIX offsets are west of UTC
+        let std_offset = -std;
+        if s.is_empty() {
+            return Some(Rule {
+                std_offset,
+                dst: None,
+            });
+        }
+        let s = name(s)?;
+        let (dst_offset, s) = match s.chars().next() {
+            Some(',') | None => (std_offset + 60 * 60, s),
+            Some(_) => {
+                let (dst, s) = time(s)?;
+                (-dst, s)
+            }
+        };
+        // no rule given, the historical default is US rules
+        let s = if s.is_empty() { ",M3.2.0,M11.1.0" } else { s };
+        let (start, start_t, s) = date(s.strip_prefix(',')?)?;
+        let (end, end_t, s) = date(s.strip_prefix(',')?)?;
+        if !s.is_empty() {
+            return None;
+        }
+        Some(Rule {
+            std_offset,
+            dst: Some((dst_offset, start, start_t, end, end_t)),
+        })
+    }
+
+    fn offset(&self, time: i64) -> i64 {
+        let Some((dst_offset, start, start_t, end, end_t)) = self.dst else {
+            return self.std_offset;
+        };
+        let (year, _, _) = civil_from_days(time.saturating_add(self.std_offset).div_euclid(DAY));
+        // transition times are given in the local time in effect before the transition
+        let start = start.days(year) * DAY + start_t - self.std_offset;
+        let end = end.days(year) * DAY + end_t - dst_offset;
+        let is_dst = if start <= end {
+            start <= time && time < end
+        } else {
+            // southern hemisphere, dst spans the new year
+            !(end <= time && time < start)
+        };
+        if is_dst {
+            dst_offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+impl RuleDate {
+    // days since epoch for this rule in `year`
+    fn days(self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        match self {
+            RuleDate::Julian1(n) => {
+                let leap = is_leap_year(year) && n >= 60;
+                jan1 + i64::from(n) - 1 + i64::from(leap)
+            }
+            RuleDate::Julian0(n) => jan1 + i64::from(n),
+            RuleDate::MonthWeekDay(m, w, d) => {
+                let first = days_from_civil(year, m, 1);
+                let mut day = 1 + (d + 7 - weekday(first)) % 7 + (w - 1) * 7;
+                while day > days_in_month(year, m) {
+                    day -= 7;
+                }
+                first + i64::from(day) - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a TZif file with the given transitions (utc time, index into offsets),
+    // in version 1 alone or version 2 with the POSIX rule as its footer
+    fn tzif(transitions: &[(i64, u8)], offsets: &[i32], footer: Option<&str>) -> Vec<u8> {
+        let block = |time_len: usize| {
+            let mut data = b"TZif".to_vec();
+            data.push(if footer.is_some() { b'2' } else { 0 });
+            data.extend([0; 15]);
+            let chars = b"ZZZ\0";
+            for n in [0, 0, 0, transitions.len(), offsets.len(), chars.len()] {
+                data.extend((n as u32).to_be_bytes());
+            }
+            for &(t, _) in transitions {
+                match time_len {
+                    4 => data.extend((t as i32).to_be_bytes()),
+                    _ => data.extend(t.to_be_bytes()),
+                }
+            }
+            data.extend(transitions.iter().map(|&(_, i)| i));
+            for &offset in offsets {
+                data.extend(offset.to_be_bytes());
+                data.extend([0, 0]);
+            }
+            data.extend(chars);
+            data
+        };
+        let mut data = block(4);
+        if let Some(footer) = footer {
+            data.extend(block(8));
+            data.extend(format!("\n{footer}\n").bytes());
+        }
+        data
+    }
+
+    // berlin's 2021 transitions, then its rule
+    const CET: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const BERLIN: &[(i64, u8)] = &[(1616893200, 1), (1635642000, 0)];
+
+    #[test]
+    fn tzif_v2() {
+        let tz = Tz::from_tzif("Europe/Berlin", &tzif(BERLIN, &[3600, 7200], Some(CET))).unwrap();
+        assert_eq!(tz.name(), "Europe/Berlin");
+        for (time, offset) in [
+            (0, 3600),
+            (1616893199, 3600),
+            (1616893200, 7200),
+            (1635641999, 7200),
+            // past the last transition the footer's rule takes over
+            (1635642000, 3600),
+            (1901149199, 3600),
+            (1901149200, 7200),
+            (1919293199, 7200),
+            (1919293200, 3600),
+        ] {
+            assert_eq!(tz.offset(time), offset, "{time}");
+        }
+    }
+
+    #[test]
+    fn tzif_v1() {
+        let tz = Tz::from_tzif("v1", &tzif(BERLIN, &[3600, 7200], None)).unwrap();
+        assert_eq!(tz.offset(1616893200), 7200);
+        // no rule, the last transition holds forever
+        assert_eq!(tz.offset(1901149200), 3600);
+    }
+
+    #[test]
+    fn tzif_invalid() {
+        let data = tzif(BERLIN, &[3600, 7200], Some(CET));
+        assert!(Tz::from_tzif("x", b"").is_none());
+        assert!(Tz::from_tzif("x", &data[..data.len() / 2]).is_none());
+        let mut magic = data.clone();
+        magic[0] = b'X';
+        assert!(Tz::from_tzif("x", &magic).is_none());
+        // a transition to an offset that isn't there
+        assert!(Tz::from_tzif("x", &tzif(&[(0, 2)], &[3600, 7200], None)).is_none());
+    }
+
+    #[test]
+    fn rules() {
+        for (rule, std, dst) in [
+            ("UTC0", 0, None),
+            ("<+0330>-3:30", 12600, None),
+            ("IST-5:30", 19800, None),
+            ("NST3:30", -12600, None),
+            ("<-03>3<-02>,M3.5.0/-2,M10.5.0/-1", -10800, Some(-7200)),
+            // dst defaults to an hour ahead and to the US rules
+            ("EST5EDT", -18000, Some(-14400)),
+            ("CET-1CEST,M3.5.0,M10.5.0/3", 3600, Some(7200)),
+            ("NZST-12NZDT-13:45,M9.5.0,M4.1.0/3", 43200, Some(49500)),
+        ] {
+            let parsed = Rule::parse(rule).unwrap_or_else(|| panic!("{rule}"));
+            assert_eq!(parsed.std_offset, std, "{rule}");
+            assert_eq!(parsed.dst.map(|d| d.0), dst, "{rule}");
+        }
+        for rule in [
+            "",
+            "UT0",
+            "EST",
+            "<+03",
+            "EST5EDT,M3.2.0",
+            "EST5EDT,M13.2.0,M11.1.0",
+            "EST5EDT,M3.6.0,M11.1.0",
+            "EST5EDT,M3.2.7,M11.1.0",
+            "EST5EDT,J0,J300",
+            "EST5EDT,366,300",
+            "EST5EDT,M3.2.0,M11.1.0x",
+        ] {
+            assert!(Rule::parse(rule).is_none(), "{rule}");
+        }
+    }
+
+    #[test]
+    fn rule_offsets() {
+        let us = Tz::posix("EST5EDT").unwrap();
+        for (time, offset) in [
+            (1710053999, -18000),
+            (1710054000, -14400),
+            (1730613599, -14400),
+            (1730613600, -18000),
+        ] {
+            assert_eq!(us.offset(time), offset, "{time}");
+        }
+
+        // southern hemisphere, dst spans the new year
+        let sydney = Tz::posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        for (time, offset) in [
+            (1704067200, 39600),
+            (1712419199, 39600),
+            (1712419200, 36000),
+            (1728143999, 36000),
+            (1728144000, 39600),
+            (1735689600, 39600),
+        ] {
+            assert_eq!(sydney.offset(time), offset, "{time}");
+        }
+    }
+
+    #[test]
+    fn rule_dates() {
+        let (y2023, y2024) = (19358, days_from_civil(2024, 1, 1));
+        for (date, year, days) in [
+            // Jn never counts february 29th, J60 is always march 1st
+            (RuleDate::Julian1(1), 2024, y2024),
+            (RuleDate::Julian1(59), 2024, 19782 - 1),
+            (RuleDate::Julian1(60), 2024, 19783),
+            (RuleDate::Julian1(60), 2023, 19417),
+            (RuleDate::Julian1(365), 2024, y2024 + 365),
+            // n does, 59 is february 29th in leap years
+            (RuleDate::Julian0(0), 2023, y2023),
+            (RuleDate::Julian0(59), 2024, 19782),
+            (RuleDate::Julian0(59), 2023, 19417),
+            // second sunday of march, last sunday of march, first monday
+            (RuleDate::MonthWeekDay(3, 2, 0), 2024, 19792),
+            (
+                RuleDate::MonthWeekDay(3, 5, 0),
+                2024,
+                days_from_civil(2024, 3, 31),
+            ),
+            (
+                RuleDate::MonthWeekDay(4, 1, 1),
+                2024,
+                days_from_civil(2024, 4, 1),
+            ),
+            // a month with only four of that weekday
+            (
+                RuleDate::MonthWeekDay(2, 5, 0),
+                2023,
+                days_from_civil(2023, 2, 26),
+            ),
+        ] {
+            assert_eq!(date.days(year), days, "{date:?} {year}");
+        }
+    }
+}